@@ -1,109 +1,744 @@
-use crate::fio::FileIO;
-use super::utils::Utility;
-use super::constants::Val;
+use rhsync_diff_tool::{Chunker, WeakHash, StrongAlgorithm, FileIO, MapMode, auto_chunk_size};
+use rhsync_diff_tool::error::{Error, Result};
+use rhsync_diff_tool::{Val, Exit};
+
+#[derive(Debug)]
+pub struct SharedError;
+
+impl SharedError {
+    pub const CHUNK_SIZE_ZERO: &'static str = "Chunk size must be non zero!";
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Command {
+    // ORIG SIG
+    Signature,
+    // SIG NEW DELTA
+    Delta,
+    // ORIG DELTA OUT
+    Patch,
+    // ORIG NEW, signature + delta in one go
+    Diff,
+}
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "signature" => Some(Self::Signature),
+            "delta" => Some(Self::Delta),
+            "patch" => Some(Self::Patch),
+            "diff" => Some(Self::Diff),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Signature => "signature",
+            Self::Delta => "delta",
+            Self::Patch => "patch",
+            Self::Diff => "diff",
+        }
+    }
+}
+
+// how diff prints its result
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    // delta list as is
+    Debug,
+    // diff -u style, for text files
+    Unified,
+    // delta and its numbers, for other tools
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "debug" => Some(Self::Debug),
+            "unified" => Some(Self::Unified),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
 
 pub struct CmdArgs {
-    pub chunk_size: usize,
+    pub program: String,
+    pub command: Command,
+    pub show_help: bool,
+    // None means auto, picked from size of original file
+    pub chunk_size: Option<usize>,
+    pub chunker: Chunker,
+    pub weak_hash: WeakHash,
+    pub strong_hash: StrongAlgorithm,
+    pub strong_len: Option<usize>,
+    pub seed: Option<u64>,
+    pub threads: usize,
+    pub map_mode: MapMode,
+    pub format: Format,
+    // prints numbers and timings of run to stderr
+    pub stats: bool,
     pub original_file_size: usize,
     pub modified_file_size: usize,
     pub original_file_path: String,
     pub modified_file_path: String,
+    pub signature_file_path: String,
+    pub delta_file_path: String,
+    pub patched_file_path: Option<String>,
+    pub reverse_delta_file_path: Option<String>,
 }
 
 impl CmdArgs {
     pub fn new() -> Self {
         Self {
+            program: "rhsync-diff-tool".to_owned(),
+            command: Command::Diff,
+            show_help: false,
             original_file_size: 0,
             modified_file_size: 0,
-            chunk_size: Val::DEFAULT_C_SIZE,
+            chunk_size: None,
+            chunker: Chunker::Fixed,
+            weak_hash: WeakHash::Adler32,
+            strong_hash: StrongAlgorithm::Xxh3_64,
+            strong_len: None,
+            seed: None,
+            threads: 1,
+            map_mode: MapMode::Auto,
+            format: Format::Debug,
+            stats: false,
             original_file_path: "".to_owned(),
             modified_file_path: "".to_owned(),
+            signature_file_path: "".to_owned(),
+            delta_file_path: "".to_owned(),
+            patched_file_path: None,
+            reverse_delta_file_path: None,
         }
     }
-    
-    pub fn parse(&mut self) -> Option<()>{
+
+    pub fn parse(&mut self) -> Result<()>{
         let mut args = std::env::args();
-        let program = args.nth(0).unwrap();
-        let file1 = args.nth(0); 
-        let file2 = args.nth(0);
-        let opt_arg = args.nth(0);
-
-        if file1.is_none() || file2.is_none() {
-            Self::print_usage(program);
-            return None;
-        }
-
-        self.original_file_path = file1.unwrap();    
-        self.modified_file_path = file2.unwrap();   
-        
-
-        // here check files exist, and chunk size validity 
-        let res = FileIO::get_file_size(&self.original_file_path);
-        if res.is_none() {
-            return None;
-        }
-        self.original_file_size = res.unwrap();
-        
-        let res = FileIO::get_file_size(&self.modified_file_path);
-        
-        if res.is_none() {
-            return None;
-        }
-        self.modified_file_size = res.unwrap();
-
-        if let Some(csz) = opt_arg {
-            let parsed = csz.parse::<usize>();
-            if parsed.is_ok() {
-                self.chunk_size = parsed.unwrap();
-            } else {
-                println!("{:?}", parsed);
-                Self::print_usage(program);
-                return None;
+        if let Some(program) = args.next() {
+            self.program = program;
+        }
+        self.parse_from(args.collect())?;
+        if self.show_help {
+            return Ok(());
+        }
+        self.check_files()
+    }
+
+    // parses args (without program name), files are not touched here
+    pub fn parse_from(&mut self, args: Vec<String>) -> Result<()> {
+        let mut args = args.into_iter().peekable();
+
+        // no sub-command means one-shot diff, as it always was
+        if let Some(cmd) = args.peek().and_then(|a| Command::from_name(a)) {
+            self.command = cmd;
+            args.next();
+        }
+
+        let mut positional = Vec::<String>::new();
+        let mut chunk_size_flag = false;
+        let mut cdc_flag = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    self.show_help = true;
+                    return Ok(());
+                },
+                "-c" | "--chunk-size" if self.takes_chunk_size() => {
+                    let csz = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.parse_chunk_size(&csz)?;
+                    chunk_size_flag = true;
+                },
+                "--cdc" if self.takes_chunk_size() => {
+                    let sizes = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.parse_cdc(&sizes)?;
+                    cdc_flag = true;
+                },
+                "--weak-hash" if self.takes_chunk_size() => {
+                    let name = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.weak_hash = WeakHash::from_name(&name).ok_or_else(
+                        || Error::Usage(format!("unknown weak hash: {}", name))
+                    )?;
+                },
+                "--strong-hash" if self.takes_chunk_size() => {
+                    let name = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.strong_hash = StrongAlgorithm::from_name(&name).ok_or_else(
+                        || Error::Usage(format!("unknown strong hash: {}", name))
+                    )?;
+                },
+                "--strong-len" if self.takes_chunk_size() => {
+                    let len = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.strong_len = Some(len.parse::<usize>().map_err(
+                        |e| Error::Usage(format!("{}: {}", len, e))
+                    )?);
+                },
+                "--seed" if self.takes_chunk_size() => {
+                    let seed = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.seed = Some(seed.parse::<u64>().map_err(
+                        |e| Error::Usage(format!("{}: {}", seed, e))
+                    )?);
+                },
+                "--threads" if self.takes_threads() => {
+                    let threads = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.threads = match threads.parse::<usize>() {
+                        Ok(0) => return Err(Error::Usage("--threads must be at least 1".to_owned())),
+                        Ok(n) => n,
+                        Err(e) => return Err(Error::Usage(format!("{}: {}", threads, e))),
+                    };
+                },
+                "--stats" => self.stats = true,
+                "--mmap" if self.takes_threads() => self.map_mode = MapMode::Always,
+                "--no-mmap" if self.takes_threads() => self.map_mode = MapMode::Never,
+                "--format" if self.command == Command::Diff => {
+                    let name = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.format = Format::from_name(&name).ok_or_else(
+                        || Error::Usage(format!("unknown format: {}", name))
+                    )?;
+                },
+                "-r" | "--reverse" if self.takes_reverse() => {
+                    let path = args.next().ok_or_else(
+                        || Error::Usage(format!("missing value for {}", arg))
+                    )?;
+                    self.reverse_delta_file_path = Some(path);
+                },
+                // "-" alone is kept as positional
+                a if a.len() > 1 && a.starts_with('-') => {
+                    return Err(Error::Usage(format!("unknown option: {}", a)));
+                },
+                _ => positional.push(arg),
+            }
+        }
+
+        if chunk_size_flag && cdc_flag {
+            return Err(Error::Usage("--chunk-size and --cdc can't be used together".to_owned()));
+        }
+        if let Some(len) = self.strong_len {
+            if !self.strong_hash.check_len(len) {
+                return Err(Error::Usage(format!(
+                    "--strong-len for {} must be within {}..={}",
+                    self.strong_hash.name(),
+                    Val::MIN_STRONG_LEN,
+                    self.strong_hash.digest_len()
+                )));
             }
         }
-        if !self.has_enough_chunks(self.original_file_size) {
-            println!(
-                "File: {}, must have at-least {} chunks!", 
-                self.original_file_path, 
-                Val::MIN_NUM_OF_CHUNKS
-            );
-            return None;
+        let chunk_size_flag = chunk_size_flag || cdc_flag;
+        let (min, max) = self.positional_range(chunk_size_flag);
+        if positional.len() < min || positional.len() > max {
+            return Err(Error::Usage(format!(
+                "wrong number of arguments for {}",
+                self.command.name()
+            )));
         }
+        let mut positional = positional.into_iter();
+        let mut next = || positional.next().unwrap_or_default();
 
-        if !self.has_enough_chunks(self.modified_file_size) {
-            println!(
-                "File: {}, must have at-least {} chunks!", 
-                self.modified_file_path, 
-                Val::MIN_NUM_OF_CHUNKS
-            );
-            return None;
+        match self.command {
+            Command::Signature => {
+                self.original_file_path = next();
+                self.signature_file_path = next();
+            },
+            Command::Delta => {
+                self.signature_file_path = next();
+                self.modified_file_path = next();
+                self.delta_file_path = next();
+            },
+            Command::Patch => {
+                self.original_file_path = next();
+                self.delta_file_path = next();
+                self.patched_file_path = Some(next());
+            },
+            Command::Diff => {
+                self.original_file_path = next();
+                self.modified_file_path = next();
+                // legacy positional chunk size
+                if !chunk_size_flag {
+                    let csz = next();
+                    if !csz.is_empty() {
+                        self.parse_chunk_size(&csz)?;
+                    }
+                }
+                let patched = next();
+                if !patched.is_empty() {
+                    self.patched_file_path = Some(patched);
+                }
+            },
         }
+        self.check_stdio()
+    }
 
-        Some(())
+    // "-" reads stdin or writes stdout, each can be used once only
+    fn check_stdio(&self) -> Result<()> {
+        let inputs = [
+            self.original_file_path.as_str(),
+            self.signature_file_path.as_str(),
+            self.modified_file_path.as_str(),
+            self.delta_file_path.as_str(),
+        ];
+        let outputs = [
+            self.signature_file_path.as_str(),
+            self.delta_file_path.as_str(),
+            self.patched_file_path.as_deref().unwrap_or_default(),
+            self.reverse_delta_file_path.as_deref().unwrap_or_default(),
+        ];
+        let (inputs, outputs) = match self.command {
+            Command::Signature => (&inputs[..1], &outputs[..1]),
+            Command::Delta => (&inputs[1..3], &outputs[1..2]),
+            Command::Patch => (&[inputs[0], inputs[3]][..], &outputs[2..]),
+            // prints delta list, and may read file_2 twice
+            Command::Diff => (&inputs[..], &outputs[..]),
+        };
+        let stdin = inputs.iter().filter(|path| FileIO::is_stdio(path)).count();
+        let stdout = outputs.iter().filter(|path| FileIO::is_stdio(path)).count();
+        if self.command == Command::Diff && stdin + stdout > 0 {
+            return Err(Error::Usage("diff can't use stdin or stdout, use delta and patch".to_owned()));
+        }
+        if stdin > 1 || stdout > 1 {
+            return Err(Error::Usage("\"-\" can stand for one input and one output only".to_owned()));
+        }
+        Ok(())
     }
 
-    fn print_usage(program: String) {
-        println!("
-        USAGE: {0} <file_1_path> <file_2_path> <optional chunk_size>
-        
-        Examples:
-            {0} abc.txt def.txt
-            {0} some.txt other.txt 4
-            {0} some.bin other.bin 7
-        
-        ", program);
+    // here check files exist, and pick auto chunk size
+    // stdin has no size, it's taken as it comes
+    fn check_files(&mut self) -> Result<()> {
+        let size_of = |path: &str| match FileIO::is_stdio(path) {
+            true => Ok(None),
+            false => FileIO::get_file_size(path).map(Some),
+        };
+        match self.command {
+            Command::Signature => match size_of(&self.original_file_path)? {
+                Some(size) => {
+                    self.original_file_size = size;
+                    self.resolve_chunk_size(size);
+                },
+                // nothing to pick chunk size from
+                None if self.chunk_size.is_none() => return Err(Error::Usage(
+                    "chunk size can't be picked for stdin, give -c <chunk_size> or --cdc <sizes>".to_owned()
+                )),
+                None => {},
+            },
+            Command::Delta => {
+                self.modified_file_size = size_of(&self.modified_file_path)?.unwrap_or_default();
+            },
+            Command::Patch => {
+                self.original_file_size = size_of(&self.original_file_path)?.unwrap_or_default();
+            },
+            Command::Diff => {
+                self.original_file_size = FileIO::get_file_size(&self.original_file_path)?;
+                self.modified_file_size = FileIO::get_file_size(&self.modified_file_path)?;
+                self.resolve_chunk_size(self.original_file_size);
+            },
+        }
+        Ok(())
     }
 
-    fn has_enough_chunks(&self, f_size: usize) -> bool {
-        Utility::get_num_of_chunks(
-            f_size, 
-            self.chunk_size
-        ) >= Val::MIN_NUM_OF_CHUNKS
+    // auto chunk size from size of original, once it's known
+    fn resolve_chunk_size(&mut self, f_size: usize) {
+        if self.chunk_size.is_none() {
+            self.chunk_size = Some(auto_chunk_size(f_size));
+        }
+    }
+
+    fn takes_chunk_size(&self) -> bool {
+        self.command == Command::Signature || self.command == Command::Diff
+    }
+
+    fn takes_threads(&self) -> bool {
+        self.command != Command::Patch
+    }
+
+    fn takes_reverse(&self) -> bool {
+        self.command == Command::Patch || self.command == Command::Diff
+    }
+
+    fn positional_range(&self, chunk_size_flag: bool) -> (usize, usize) {
+        match self.command {
+            Command::Signature => (2, 2),
+            Command::Delta => (3, 3),
+            Command::Patch => (3, 3),
+            // chunk size can't be given twice
+            Command::Diff if chunk_size_flag => (2, 3),
+            Command::Diff => (2, 4),
+        }
+    }
+
+    fn parse_chunk_size(&mut self, csz: &str) -> Result<()> {
+        if csz == "auto" {
+            self.chunk_size = None;
+            return Ok(());
+        }
+        match csz.parse::<usize>() {
+            Ok(parsed) if parsed > 0 => {
+                self.chunk_size = Some(parsed);
+                Ok(())
+            },
+            Ok(_) => Err(Error::InvalidChunkSize(
+                SharedError::CHUNK_SIZE_ZERO.to_owned()
+            )),
+            Err(e) => Err(Error::InvalidChunkSize(format!("{}: {}", csz, e))),
+        }
+    }
+
+    // "<avg>" or "<min>,<avg>,<max>"
+    fn parse_cdc(&mut self, sizes: &str) -> Result<()> {
+        let sizes = sizes
+            .split(',')
+            .map(|size| size.parse::<usize>().map_err(
+                |e| Error::InvalidChunkSize(format!("{}: {}", size, e))
+            ))
+            .collect::<Result<Vec<usize>>>()?;
+        let (chunker, avg) = match sizes[..] {
+            [avg] => (Chunker::gear(avg), avg),
+            [min, avg, max] => (Chunker::Gear { min, max }, avg),
+            _ => return Err(Error::Usage(
+                "--cdc takes <avg> or <min>,<avg>,<max>".to_owned()
+            )),
+        };
+        chunker.check(avg)?;
+        self.chunker = chunker;
+        self.chunk_size = Some(avg);
+        Ok(())
+    }
+
+    pub fn usage(&self) -> String {
+        let program = &self.program;
+        let usage = match self.command {
+            Command::Signature => format!("
+        USAGE: {0} signature [-c|--chunk-size <chunk_size>|auto] [--cdc <sizes>] [--weak-hash <name>] [--strong-hash <name>] [--strong-len <bytes>] [--seed <n>] [--threads <n>] [--mmap|--no-mmap] [--stats] <original_file> <signature_file>
+
+        Computes signature of original file and writes it to signature file,
+        \"-\" for either one stands for stdin/stdout.
+        chunk size is auto unless given: square root of file size, {1} to {2} bytes,
+        original read from stdin has no size, it needs -c or --cdc.
+        with --cdc, chunks are content defined instead of fixed size,
+        sizes are <avg> or <min>,<avg>,<max> bytes.
+        --weak-hash picks rolling hash: adler32 (default), rabin-karp or buzhash.
+        --strong-hash picks hash confirming matches: xxh3-64 (default), xxh3-128, blake3 or sha256,
+        --strong-len keeps only that many bytes of it per chunk (smaller signature, more collisions).
+        strong hash is seeded with a random number unless --seed gives one, for reproducible output.
+        --threads hashes chunks on that many threads, signature is the same either way.
+        files of 64 MiB or more are memory mapped, --mmap maps any regular file, --no-mmap none.
+        --stats prints signature size and time taken to stderr.
+
+        Examples:
+            {0} signature abc.txt abc.sig
+            {0} signature -c 7 some.bin some.sig
+            {0} signature --cdc 8192 some.bin some.sig
+            {0} signature --cdc 2048,8192,65536 some.bin some.sig
+            {0} signature --weak-hash buzhash some.bin some.sig
+            {0} signature --strong-hash sha256 --strong-len 16 some.bin some.sig
+            {0} signature --seed 42 some.bin some.sig
+            {0} signature --threads 8 --cdc 65536 big.iso big.sig
+        ", program, Val::MIN_AUTO_C_SIZE, Val::MAX_AUTO_C_SIZE),
+            Command::Delta => format!("
+        USAGE: {0} delta [--threads <n>] [--mmap|--no-mmap] [--stats] <signature_file> <modified_file> <delta_file>
+
+        Computes delta of modified file against signature and writes it to delta file,
+        \"-\" for modified file reads stdin, for delta file writes stdout.
+        chunker, chunk size, weak and strong hash and seed are taken from signature file.
+        --threads searches segments of modified file on that many threads, delta is the same either way.
+        modified file is memory mapped as with signature, see --mmap and --no-mmap.
+        --stats prints matched chunks, literal bytes, deleted chunks, delta size,
+        weak hash false positives and time taken by each step to stderr.
+
+        Examples:
+            {0} delta abc.sig def.txt abc_def.delta
+            {0} delta --threads 8 big.sig big_new.iso big.delta
+            {0} delta --stats abc.sig def.txt abc_def.delta
+            tar c dir | {0} delta dir.sig - - | ssh host {0} patch dir.tar - new.tar
+        ", program),
+            Command::Patch => format!("
+        USAGE: {0} patch [-r|--reverse <reverse_delta_file>] [--stats] <original_file> <delta_file> <patched_file>
+
+        Applies delta file to original file and writes result to patched file,
+        \"-\" for original or delta file (not both) reads stdin, for patched file writes stdout.
+        with --reverse, delta turning patched file back into original is written too,
+        apply it with patch to roll back.
+        --stats prints delta size and time taken to stderr.
+
+        Examples:
+            {0} patch abc.txt abc_def.delta def.txt
+            {0} patch -r def_abc.delta abc.txt abc_def.delta def.txt
+            {0} patch def.txt def_abc.delta abc.txt
+            cat abc.txt | {0} patch - abc_def.delta def.txt
+        ", program),
+            Command::Diff => format!("
+        USAGE: {0} [diff] [-c|--chunk-size <chunk_size>|auto] [--cdc <sizes>] [--weak-hash <name>] [--strong-hash <name>] [--strong-len <bytes>] [--seed <n>] [--threads <n>] [--mmap|--no-mmap] [--format <name>] [--stats] [-r|--reverse <reverse_delta_file>] <file_1_path> <file_2_path> <optional chunk_size> <optional patched_file_path>
+
+        Computes signature and delta in one go and prints delta list,
+        if patched_file_path is provided, file_1 is patched into it.
+        with --reverse, delta turning file_2 back into file_1 is written too.
+        --format picks how delta is printed: debug (default) lists changes as they are,
+        unified shows them as lines removed and added, like diff -u, for text files,
+        json writes files, chunk size, changes (literals in base64) and a summary of them.
+        --stats prints numbers of signature and delta, and time taken by each step to stderr.
+
+        Sub-commands:
+            signature   <original_file> <signature_file>
+            delta       <signature_file> <modified_file> <delta_file>
+            patch       <original_file> <delta_file> <patched_file>
+            diff        <file_1_path> <file_2_path>
+            use `{0} <sub-command> --help` for details
+
+        Examples:
+            {0} abc.txt def.txt
+            {0} diff some.txt other.txt 4
+            {0} diff -c 7 some.bin other.bin
+            {0} diff --cdc 4096 some.bin other.bin
+            {0} diff --format unified old.conf new.conf
+            {0} diff --format json some.bin other.bin > delta.json
+            {0} some.bin other.bin 7 patched.bin
+        ", program),
+        };
+        format!("{}
+        Exit codes: {} ok, {} failure, {} wrong usage, {} bad signature/delta file, {} checksum mismatch
+        ", usage, Exit::OK, Exit::FAILURE, Exit::USAGE, Exit::BAD_INPUT, Exit::MISMATCH)
     }
 }
 
 
 #[cfg(test)]
+#[allow(non_snake_case)]
 mod common_test {
-    
-}
\ No newline at end of file
+    use super::*;
+
+    fn parse(args: &[&str]) -> (Result<()>, CmdArgs) {
+        let mut cmd_args = CmdArgs::new();
+        let res = cmd_args.parse_from(
+            args.iter().map(|a| a.to_string()).collect()
+        );
+        (res, cmd_args)
+    }
+
+    #[test]
+    fn ut_parseSignature_works() {
+        let (res, args) = parse(&["signature", "-c", "8", "a.txt", "a.sig"]);
+        assert!(res.is_ok());
+        assert_eq!(args.command, Command::Signature);
+        assert_eq!(args.chunk_size, Some(8));
+        assert_eq!(args.original_file_path, "a.txt");
+        assert_eq!(args.signature_file_path, "a.sig");
+    }
+
+    #[test]
+    fn ut_parseDeltaAndPatch_works() {
+        let (res, args) = parse(&["delta", "a.sig", "b.txt", "ab.delta"]);
+        assert!(res.is_ok());
+        assert_eq!(args.command, Command::Delta);
+        assert_eq!(args.signature_file_path, "a.sig");
+        assert_eq!(args.modified_file_path, "b.txt");
+        assert_eq!(args.delta_file_path, "ab.delta");
+
+        let (res, args) = parse(&["patch", "a.txt", "ab.delta", "b.txt"]);
+        assert!(res.is_ok());
+        assert_eq!(args.command, Command::Patch);
+        assert_eq!(args.patched_file_path, Some("b.txt".to_owned()));
+        assert_eq!(args.reverse_delta_file_path, None);
+
+        let (res, args) = parse(&["patch", "-r", "ba.delta", "a.txt", "ab.delta", "b.txt"]);
+        assert!(res.is_ok());
+        assert_eq!(args.reverse_delta_file_path, Some("ba.delta".to_owned()));
+        assert!(matches!(parse(&["delta", "-r", "x", "a", "b", "c"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseLegacyDiff_works() {
+        let (res, args) = parse(&["a.txt", "b.txt", "7", "out.txt"]);
+        assert!(res.is_ok());
+        assert_eq!(args.command, Command::Diff);
+        assert_eq!(args.chunk_size, Some(7));
+        assert_eq!(args.patched_file_path, Some("out.txt".to_owned()));
+
+        let (res, args) = parse(&["diff", "-c", "7", "a.txt", "b.txt", "out.txt"]);
+        assert!(res.is_ok());
+        assert_eq!(args.chunk_size, Some(7));
+        assert_eq!(args.patched_file_path, Some("out.txt".to_owned()));
+    }
+
+    #[test]
+    fn ut_parseAutoChunkSize_works() {
+        assert_eq!(parse(&["signature", "a.txt", "a.sig"]).1.chunk_size, None);
+        assert_eq!(parse(&["signature", "-c", "auto", "a.txt", "a.sig"]).1.chunk_size, None);
+        assert_eq!(parse(&["a.txt", "b.txt", "auto", "out.txt"]).1.chunk_size, None);
+
+        // picked once size of original is known
+        let mut args = CmdArgs::new();
+        args.command = Command::Signature;
+        args.original_file_path = "./test/files/original.dat".to_owned();
+        args.check_files().unwrap();
+        assert_eq!(args.chunk_size, Some(Val::MIN_AUTO_C_SIZE));
+
+        // stdin has no size, chunk size must be given
+        let (_, mut args) = parse(&["signature", "-", "a.sig"]);
+        assert!(matches!(args.check_files(), Err(Error::Usage(_))));
+        let (_, mut args) = parse(&["signature", "-c", "4096", "-", "a.sig"]);
+        args.check_files().unwrap();
+        assert_eq!(args.chunk_size, Some(4096));
+        let (_, mut args) = parse(&["signature", "--cdc", "4096", "-", "a.sig"]);
+        assert!(args.check_files().is_ok());
+    }
+
+    #[test]
+    fn ut_parseCdc_works() {
+        let (res, args) = parse(&["signature", "--cdc", "1024", "a.txt", "a.sig"]);
+        assert!(res.is_ok());
+        assert_eq!(args.chunk_size, Some(1024));
+        assert_eq!(args.chunker, Chunker::gear(1024));
+
+        let (res, args) = parse(&["diff", "--cdc", "16,64,256", "a.txt", "b.txt", "out.txt"]);
+        assert!(res.is_ok());
+        assert_eq!(args.chunk_size, Some(64));
+        assert_eq!(args.chunker, Chunker::Gear { min: 16, max: 256 });
+        assert_eq!(args.patched_file_path, Some("out.txt".to_owned()));
+
+        assert!(matches!(parse(&["signature", "--cdc", "64,16,256", "a", "b"]).0, Err(Error::InvalidChunkSize(_))));
+        assert!(matches!(parse(&["signature", "--cdc", "0,16,256", "a", "b"]).0, Err(Error::InvalidChunkSize(_))));
+        assert!(matches!(parse(&["signature", "--cdc", "16,64,32", "a", "b"]).0, Err(Error::InvalidChunkSize(_))));
+        assert!(matches!(parse(&["signature", "--cdc", "1,2", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["signature", "--cdc", "8", "-c", "8", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["delta", "--cdc", "8", "a", "b", "c"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseWeakHash_works() {
+        let (res, args) = parse(&["signature", "--weak-hash", "rabin-karp", "a.txt", "a.sig"]);
+        assert!(res.is_ok());
+        assert_eq!(args.weak_hash, WeakHash::RabinKarp);
+
+        let (res, args) = parse(&["diff", "a.txt", "b.txt"]);
+        assert!(res.is_ok());
+        assert_eq!(args.weak_hash, WeakHash::Adler32);
+
+        assert!(matches!(parse(&["signature", "--weak-hash", "crc", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["patch", "--weak-hash", "buzhash", "a", "b", "c"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseStrongHash_works() {
+        let (res, args) = parse(&["signature", "--strong-hash", "sha256", "--strong-len", "16", "a", "b"]);
+        assert!(res.is_ok());
+        assert_eq!(args.strong_hash, StrongAlgorithm::Sha256);
+        assert_eq!(args.strong_len, Some(16));
+
+        // length is checked against chosen hash, whatever the order
+        assert!(parse(&["diff", "--strong-len", "32", "--strong-hash", "blake3", "a", "b"]).0.is_ok());
+        assert!(matches!(parse(&["diff", "--strong-len", "16", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["signature", "--strong-hash", "md5", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["signature", "--strong-len", "x", "a", "b"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseSeed_works() {
+        let (res, args) = parse(&["signature", "--seed", "42", "a", "b"]);
+        assert!(res.is_ok());
+        assert_eq!(args.seed, Some(42));
+
+        assert_eq!(parse(&["diff", "a", "b"]).1.seed, None);
+        assert!(matches!(parse(&["signature", "--seed", "-1", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["delta", "--seed", "1", "a", "b", "c"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseFormat_works() {
+        let (res, args) = parse(&["diff", "--format", "unified", "a", "b"]);
+        assert!(res.is_ok());
+        assert_eq!(args.format, Format::Unified);
+        assert_eq!(parse(&["diff", "--format", "json", "a", "b"]).1.format, Format::Json);
+
+        assert_eq!(parse(&["a", "b"]).1.format, Format::Debug);
+        assert!(matches!(parse(&["diff", "--format", "html", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["delta", "--format", "unified", "a", "b", "c"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseStats_works() {
+        assert!(!parse(&["diff", "a", "b"]).1.stats);
+        for args in [
+            &["signature", "--stats", "a", "b"][..],
+            &["delta", "--stats", "a", "b", "c"],
+            &["patch", "--stats", "a", "b", "c"],
+            &["diff", "--stats", "a", "b"],
+        ] {
+            let (res, args) = parse(args);
+            assert!(res.is_ok());
+            assert!(args.stats);
+        }
+    }
+
+    #[test]
+    fn ut_parseThreads_works() {
+        let (res, args) = parse(&["signature", "--threads", "4", "a", "b"]);
+        assert!(res.is_ok());
+        assert_eq!(args.threads, 4);
+
+        assert_eq!(parse(&["diff", "a", "b"]).1.threads, 1);
+        assert!(matches!(parse(&["signature", "--threads", "0", "a", "b"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["signature", "--threads", "many", "a", "b"]).0, Err(Error::Usage(_))));
+        assert_eq!(parse(&["delta", "--threads", "2", "a", "b", "c"]).1.threads, 2);
+        assert!(matches!(parse(&["patch", "--threads", "2", "a", "b", "c"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseMapMode_works() {
+        assert_eq!(parse(&["signature", "a", "b"]).1.map_mode, MapMode::Auto);
+        assert_eq!(parse(&["signature", "--mmap", "a", "b"]).1.map_mode, MapMode::Always);
+        assert_eq!(parse(&["diff", "--no-mmap", "a", "b"]).1.map_mode, MapMode::Never);
+        assert!(matches!(parse(&["patch", "--mmap", "a", "b", "c"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseStdio_works() {
+        let (res, args) = parse(&["delta", "a.sig", "-", "-"]);
+        assert!(res.is_ok());
+        assert!(FileIO::is_stdio(&args.modified_file_path));
+        assert!(FileIO::is_stdio(&args.delta_file_path));
+        assert!(parse(&["patch", "a.txt", "-", "-"]).0.is_ok());
+        assert!(parse(&["patch", "-", "a.delta", "-"]).0.is_ok());
+        assert!(parse(&["signature", "-", "a.sig"]).0.is_ok());
+
+        // one stdin, one stdout at most
+        assert!(matches!(parse(&["delta", "-", "-", "a.delta"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["patch", "-", "-", "b.txt"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["patch", "-r", "-", "a", "b", "-"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["diff", "a.txt", "-"]).0, Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_parseHelp_works() {
+        let (res, args) = parse(&["patch", "--help"]);
+        assert!(res.is_ok());
+        assert!(args.show_help);
+        assert_eq!(args.command, Command::Patch);
+    }
+
+    #[test]
+    fn ut_parseWrongUsage_fails() {
+        assert!(matches!(parse(&["delta", "a.sig", "b.txt"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["patch", "-c", "4", "a", "b", "c"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["signature", "-c", "0", "a", "b"]).0, Err(Error::InvalidChunkSize(_))));
+        assert!(matches!(parse(&["diff", "a", "b", "x"]).0, Err(Error::InvalidChunkSize(_))));
+        assert!(matches!(parse(&["diff", "-c", "4", "a", "b", "c", "d"]).0, Err(Error::Usage(_))));
+        assert!(matches!(parse(&["diff", "--bogus", "a", "b"]).0, Err(Error::Usage(_))));
+    }
+}
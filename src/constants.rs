@@ -1,21 +1,22 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
-#![allow(non_camel_case_types)]
-
-#[derive(Debug, PartialEq, Clone)]
-pub enum ErrKind {
-    FILE_OPEN,
-
-}
-
-
 #[non_exhaustive]
 #[derive(Debug)]
 pub struct Val;
 
+// process exit codes
+#[non_exhaustive]
 #[derive(Debug)]
-pub struct SharedError;
+pub struct Exit;
+
+impl Exit {
+    pub const OK: i32 = 0;
+    pub const FAILURE: i32 = 1;
+    pub const USAGE: i32 = 2;
+    // corrupt or unsupported signature/delta file
+    pub const BAD_INPUT: i32 = 3;
+    pub const MISMATCH: i32 = 4;
+}
 
+#[cfg(test)]
 impl Val {
     pub const ZERO_U8: u8 = 0;
     pub const ZERO_U16: u16 = 0;
@@ -26,11 +27,40 @@ impl Val {
     pub const TEST_INDEX: usize = 1;
     pub const TEST_C_SIZE: usize = 4;
     pub const TEST_F_SIZE: usize = 80;
-    pub const DEFAULT_C_SIZE: usize = 4;
-    pub const MIN_NUM_OF_CHUNKS: usize = 2;
 }
 
-impl SharedError {
-    pub const CHUNK_SIZE_ZERO: &'static str = "Chunk size must be non zero!";
-    pub const AT_LEAST_TWO_CHUNKS: &'static str = "chunk size invalid, must have at least 2 chunks for the file";
+impl Val {
+    // bounds of automatic chunk size, same as rsync
+    pub const MIN_AUTO_C_SIZE: usize = 700;
+    pub const MAX_AUTO_C_SIZE: usize = 128 * 1024;
+    // size of buffer used when streaming files
+    pub const READ_BUF_SIZE: usize = 64 * 1024;
+    // unmatched bytes held before they are passed on as a literal
+    pub const LITERAL_FLUSH_SIZE: usize = 64 * 1024;
+    // path standing for stdin/stdout
+    pub const STDIO_PATH: &'static str = "-";
+    // input files this big are memory mapped unless told otherwise
+    pub const MMAP_THRESHOLD: usize = 64 * 1024 * 1024;
+    // bytes of chunks each thread hashes in one go
+    pub const THREAD_BATCH_SIZE: usize = 1024 * 1024;
+    pub const DELTA_MAGIC: [u8; 4] = *b"RHSD";
+    pub const DELTA_VERSION: u8 = 7;
+    pub const SIGN_MAGIC: [u8; 4] = *b"RHSS";
+    pub const SIGN_VERSION: u8 = 5;
+    // hash algorithm ids recorded in signature file
+    pub const WEAK_HASH_ADLER32: u8 = 1;
+    pub const WEAK_HASH_RABIN_KARP: u8 = 2;
+    pub const WEAK_HASH_BUZHASH: u8 = 3;
+    pub const STRONG_HASH_XXH3_64: u8 = 1;
+    pub const STRONG_HASH_XXH3_128: u8 = 2;
+    pub const STRONG_HASH_BLAKE3: u8 = 3;
+    pub const STRONG_HASH_SHA256: u8 = 4;
+    // bounds of strong hash length kept per chunk
+    pub const MIN_STRONG_LEN: usize = 2;
+    pub const MAX_STRONG_LEN: usize = 32;
+    // chunker ids recorded in signature and delta files
+    pub const CHUNKER_FIXED: u8 = 0;
+    pub const CHUNKER_GEAR: u8 = 1;
+    // lines of context around changes in unified output
+    pub const UNIFIED_CONTEXT: usize = 3;
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+use std::fmt;
+use crate::constants::Exit;
+
+/// Everything that can go wrong in signature, delta and patch steps.
+#[derive(Debug)]
+pub enum Error {
+    // path is empty when error comes from a stream without name
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    // wrong command line
+    Usage(String),
+    InvalidChunkSize(String),
+    CorruptSignature(String),
+    CorruptDelta(String),
+    ChecksumMismatch {
+        what: String,
+        expected: String,
+        found: String,
+    },
+    UnsupportedVersion {
+        what: String,
+        found: u8,
+        supported: u8,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &str, source: std::io::Error) -> Self {
+        Self::Io {
+            path: path.to_owned(),
+            source,
+        }
+    }
+
+    // names the file of an i/o error which had no path yet
+    pub fn with_path(self, path: &str) -> Self {
+        match self {
+            Self::Io { path: p, source } if p.is_empty() => Self::io(path, source),
+            other => other,
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Usage(_) |
+            Self::InvalidChunkSize(_) => Exit::USAGE,
+            Self::CorruptSignature(_) |
+            Self::CorruptDelta(_) |
+            Self::UnsupportedVersion { .. } => Exit::BAD_INPUT,
+            Self::ChecksumMismatch { .. } => Exit::MISMATCH,
+            Self::Io { .. } => Exit::FAILURE,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } if path.is_empty() => write!(f, "{}", source),
+            Self::Io { path, source } => write!(f, "{}: {}", path, source),
+            Self::Usage(msg) => write!(f, "{}", msg),
+            Self::InvalidChunkSize(msg) => write!(f, "invalid chunk size: {}", msg),
+            Self::CorruptSignature(msg) => write!(f, "corrupt signature: {}", msg),
+            Self::CorruptDelta(msg) => write!(f, "corrupt delta: {}", msg),
+            Self::ChecksumMismatch { what, expected, found } => write!(
+                f,
+                "checksum mismatch for {}: expected {}, found {}",
+                what,
+                expected,
+                found
+            ),
+            Self::UnsupportedVersion { what, found, supported } => write!(
+                f,
+                "unsupported {} format version {} (supported: {})",
+                what,
+                found,
+                supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Self::io("", source)
+    }
+}
+
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+mod error_test {
+    use super::*;
+
+    #[test]
+    fn ut_withPath_works() {
+        let err = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+        assert!(matches!(&err, Error::Io { path, .. } if path.is_empty()));
+
+        let err = err.with_path("a.txt");
+        assert!(matches!(&err, Error::Io { path, .. } if path == "a.txt"));
+        assert!(err.to_string().starts_with("a.txt: "));
+        // path already set stays
+        let err = err.with_path("b.txt");
+        assert!(matches!(&err, Error::Io { path, .. } if path == "a.txt"));
+    }
+
+    #[test]
+    fn ut_exitCode_works() {
+        assert_eq!(Error::Usage("x".to_owned()).exit_code(), Exit::USAGE);
+        assert_eq!(Error::CorruptDelta("x".to_owned()).exit_code(), Exit::BAD_INPUT);
+        assert_eq!(
+            Error::ChecksumMismatch {
+                what: "x".to_owned(),
+                expected: "a".to_owned(),
+                found: "b".to_owned(),
+            }.exit_code(),
+            Exit::MISMATCH
+        );
+    }
+}
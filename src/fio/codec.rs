@@ -0,0 +1,152 @@
+#![allow(non_snake_case)]
+
+/*
+    Little helpers for the on-disk formats (delta file, signature file).
+    fixed width integers are little-endian,
+    lengths and offsets are written as LEB128 varints to keep files compact.
+*/
+
+pub struct Encoder {
+    pub buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+        }
+    }
+
+    pub fn put_u8(&mut self, v: u8) -> &mut Self {
+        self.buf.push(v);
+        self
+    }
+
+    pub fn put_u32(&mut self, v: u32) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn put_u64(&mut self, v: u64) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_le_bytes());
+        self
+    }
+
+    pub fn put_varint(&mut self, v: u64) -> &mut Self {
+        let mut v = v;
+        while v >= 0x80 {
+            self.buf.push((v as u8 & 0x7F) | 0x80);
+            v >>= 7;
+        }
+        self.buf.push(v as u8);
+        self
+    }
+
+    pub fn put_bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Decoder<'local> {
+    pub buf: &'local [u8],
+    pub pos: usize,
+}
+
+impl<'local> Decoder<'local> {
+    pub fn new(buf: &'local [u8]) -> Self {
+        Self {
+            buf,
+            pos: 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    pub fn get_bytes(&mut self, len: usize) -> Option<&'local [u8]> {
+        let end = self.pos.checked_add(len)?;
+        if end > self.buf.len() {
+            return None;
+        }
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        Some(bytes)
+    }
+
+    pub fn get_u8(&mut self) -> Option<u8> {
+        Some(self.get_bytes(1)?[0])
+    }
+
+    pub fn get_u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.get_bytes(4)?.try_into().ok()?))
+    }
+
+    pub fn get_u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.get_bytes(8)?.try_into().ok()?))
+    }
+
+    pub fn get_varint(&mut self) -> Option<u64> {
+        let mut v = 0u64;
+        let mut shift = 0u32;
+        loop {
+            let byte = self.get_u8()?;
+            // more than 10 bytes can't be a u64
+            if shift > 63 {
+                return None;
+            }
+            v |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(v);
+            }
+            shift += 7;
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod codec_test {
+    use super::*;
+
+    #[test]
+    fn ut_encodeDecode_works() {
+        let mut enc = Encoder::new();
+        enc.put_u8(7)
+            .put_u32(0xDEADBEEF)
+            .put_u64(u64::MAX)
+            .put_varint(0)
+            .put_varint(300)
+            .put_varint(u64::MAX)
+            .put_bytes(b"abc");
+
+        let mut dec = Decoder::new(&enc.buf);
+        assert_eq!(dec.get_u8(), Some(7));
+        assert_eq!(dec.get_u32(), Some(0xDEADBEEF));
+        assert_eq!(dec.get_u64(), Some(u64::MAX));
+        assert_eq!(dec.get_varint(), Some(0));
+        assert_eq!(dec.get_varint(), Some(300));
+        assert_eq!(dec.get_varint(), Some(u64::MAX));
+        assert_eq!(dec.get_bytes(3), Some(&b"abc"[..]));
+        assert!(dec.is_empty());
+    }
+
+    #[test]
+    fn ut_decodeTruncated_fails() {
+        let mut enc = Encoder::new();
+        enc.put_u64(1).put_varint(300);
+
+        let mut dec = Decoder::new(&enc.buf[..4]);
+        assert_eq!(dec.get_u64(), None);
+
+        let mut dec = Decoder::new(&enc.buf[8..9]);
+        assert_eq!(dec.get_varint(), None);
+    }
+}
@@ -1,47 +1,116 @@
 #![allow(unused_assignments)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+pub mod codec;
 
 use std::fs::File;
-use crate::utils::Err;
 use crate::utils::Utility;
-use std::io::{Read, BufReader};
-use crate::constants::{ErrKind};
+use crate::error::{Error, Result};
+use std::io::{self, Read, Write, BufRead, BufReader, BufWriter, ErrorKind};
+use memmap2::Mmap;
+use crate::constants::Val;
 
 pub struct FileIO;
 
+/// When an input file is memory mapped instead of streamed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MapMode {
+    // files of Val::MMAP_THRESHOLD bytes or more
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Input file, either mapped into memory or streamed.
+pub enum Input {
+    Mapped(Mmap),
+    // stdin, pipes, special files and (in auto mode) small files
+    Stream(Box<dyn BufRead>),
+}
+
 impl FileIO {
-    pub fn get_file_size(path: &str) -> Option<usize> {
-        let f = File::open(path);
-        if f.is_err() {
-            Err::handle(path, ErrKind::FILE_OPEN);
-            return None;
-        }
+    // "-" stands for stdin as input and stdout as output
+    pub fn is_stdio(path: &str) -> bool {
+        path == Val::STDIO_PATH
+    }
+
+    pub fn get_file_size(path: &str) -> Result<usize> {
+        let f = File::open(path).map_err(|e| Error::io(path, e))?;
         // get file size from file meta-data
-        Some(f.unwrap().metadata().ok()?.len() as usize)
+        let meta = f.metadata().map_err(|e| Error::io(path, e))?;
+        Ok(meta.len() as usize)
+    }
+
+    pub fn open_file_reader(path: &str) -> Result<BufReader<File>> {
+        let f = File::open(path).map_err(|e| Error::io(path, e))?;
+        Ok(BufReader::with_capacity(Val::READ_BUF_SIZE, f))
     }
 
-    pub fn read_file_to_bytes(path: &str) -> Option<Vec<u8>> {
-        let f = File::open(path);
-        if f.is_err() {
-            println!("{:#?}", f);
-            Err::handle(path, ErrKind::FILE_OPEN);
-            return None;
+    // maps file as mode says, anything that can't be mapped is streamed
+    pub fn open_input(path: &str, mode: MapMode) -> Result<Input> {
+        if Self::is_stdio(path) {
+            return Ok(Input::Stream(Box::new(io::stdin().lock())));
         }
-        let mut reader = BufReader::new(f.unwrap());
+        let f = File::open(path).map_err(|e| Error::io(path, e))?;
+        let meta = f.metadata().map_err(|e| Error::io(path, e))?;
+        let wanted = match mode {
+            MapMode::Auto => meta.len() >= Val::MMAP_THRESHOLD as u64,
+            MapMode::Always => true,
+            MapMode::Never => false,
+        };
+        // empty files can't be mapped on every platform
+        if wanted && meta.is_file() && meta.len() > 0 {
+            // SAFETY: file must not be truncated while mapped, same as rsync
+            // we trust nobody shrinks an input file under us
+            if let Ok(map) = unsafe { Mmap::map(&f) } {
+                return Ok(Input::Mapped(map));
+            }
+        }
+        Ok(Input::Stream(Box::new(BufReader::with_capacity(Val::READ_BUF_SIZE, f))))
+    }
+
+    // fills buf as much as possible, returns bytes read
+    // less than buf.len() means end of stream is reached
+    pub fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+        let mut filled = 0usize;
+        while filled < buf.len() {
+            match reader.read(&mut buf[filled..]) {
+                Ok(0) => break,
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(filled)
+    }
+
+    pub fn read_file_to_bytes(path: &str) -> Result<Vec<u8>> {
+        let mut reader: Box<dyn Read> = match Self::is_stdio(path) {
+            true => Box::new(io::stdin().lock()),
+            false => Box::new(Self::open_file_reader(path)?),
+        };
         let mut buffer = Vec::new();
-        reader.read_to_end(&mut buffer).ok()?;
+        reader.read_to_end(&mut buffer).map_err(|e| Error::io(path, e))?;
 
-        Some(buffer)
+        Ok(buffer)
     }
 
     pub fn read_file_to_chunk_list(
         path: &str, 
         c_size: usize,
         f_size: usize
-    ) -> Option<Vec<Vec<u8>>> {
-        let bytes = Self::read_file_to_bytes(path);
-        if bytes.is_none() { return None; }
-        let bytes = bytes.unwrap();
-        
+    ) -> Result<Vec<Vec<u8>>> {
+        let bytes = Self::read_file_to_bytes(path)?;
+        Ok(Self::bytes_to_chunk_list(&bytes, c_size, f_size))
+    }
+
+    pub fn bytes_to_chunk_list(
+        bytes: &[u8],
+        c_size: usize,
+        f_size: usize
+    ) -> Vec<Vec<u8>> {
         let mut i = 0usize;
         let mut s = 0usize;
         let mut e = 0usize;
@@ -63,7 +132,22 @@ impl FileIO {
             buf.push(v);
             i += 1;
         }
-        Some(buf)
+        buf
+    }
+
+    // buffered writer of file at path, or of stdout
+    pub fn create_file_writer(path: &str) -> Result<Box<dyn Write>> {
+        Ok(match Self::is_stdio(path) {
+            true => Box::new(io::stdout().lock()),
+            false => Box::new(BufWriter::new(File::create(path).map_err(|e| Error::io(path, e))?)),
+        })
+    }
+
+    pub fn write_bytes_to_file(path: &str, bytes: &[u8]) -> Result<()> {
+        let mut writer = Self::create_file_writer(path)?;
+        writer.write_all(bytes)
+            .and_then(|_| writer.flush())
+            .map_err(|e| Error::io(path, e))
     }
 }
 
@@ -87,9 +171,9 @@ mod fio_test {
         let res1 = FileIO::get_file_size(added_chars);
         let res2 = FileIO::get_file_size(removed_chars);
 
-        assert_ne!(res0, None);
-        assert_ne!(res1, None);
-        assert_ne!(res2, None);
+        assert!(res0.is_ok());
+        assert!(res1.is_ok());
+        assert!(res2.is_ok());
         assert_eq!(res0.unwrap(), original_size);
         assert_eq!(res1.unwrap(), added_chars_size);
         assert_eq!(res2.unwrap(), removed_chars_size);
@@ -98,7 +182,7 @@ mod fio_test {
     #[test]
     fn ut_fileToBytes_works() {
         let res = FileIO::read_file_to_bytes(original);
-        assert_ne!(res, None);
+        assert!(res.is_ok());
         let res = res.unwrap();
         assert_eq!(res.len(), original_size);
         assert_eq!(
@@ -114,7 +198,7 @@ mod fio_test {
             chunk_size,
             original_size
         );
-        assert_ne!(res, None);
+        assert!(res.is_ok());
         let res = res.unwrap();
         assert_eq!(
             res.len(), 
@@ -130,16 +214,61 @@ mod fio_test {
         );
     }
 
+    #[test]
+    fn ut_readFull_works() {
+        let mut reader = FileIO::open_file_reader(original).unwrap();
+        let mut buf = vec![0u8; 30];
+
+        assert_eq!(FileIO::read_full(&mut reader, &mut buf).unwrap(), 30);
+        assert_eq!(buf[..4], "samp".as_bytes()[..]);
+        assert_eq!(FileIO::read_full(&mut reader, &mut buf).unwrap(), 4);
+        assert_eq!(buf[..4], "iff.".as_bytes()[..]);
+        assert_eq!(FileIO::read_full(&mut reader, &mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn ut_openInput_works() {
+        match FileIO::open_input(original, MapMode::Always).unwrap() {
+            Input::Mapped(map) => assert_eq!(map[..], FileIO::read_file_to_bytes(original).unwrap()),
+            Input::Stream(_) => panic!("regular file is not mapped"),
+        }
+        // small file is streamed unless asked otherwise
+        assert!(matches!(FileIO::open_input(original, MapMode::Auto).unwrap(), Input::Stream(_)));
+        assert!(matches!(FileIO::open_input(original, MapMode::Never).unwrap(), Input::Stream(_)));
+        // special files fall back to streaming
+        if std::path::Path::new("/dev/null").exists() {
+            assert!(matches!(FileIO::open_input("/dev/null", MapMode::Always).unwrap(), Input::Stream(_)));
+        }
+        assert!(matches!(
+            FileIO::open_input(invalid_file, MapMode::Always),
+            Err(Error::Io { .. })
+        ));
+        // stdin is never mapped
+        assert!(matches!(FileIO::open_input("-", MapMode::Always).unwrap(), Input::Stream(_)));
+    }
+
+    #[test]
+    fn ut_bytesToFile_works() {
+        let path = std::env::temp_dir().join("rhsync_fio_test.dat");
+        let path = path.to_str().unwrap();
+        let bytes = "sample data for rolling hash diff.".as_bytes();
+
+        let res = FileIO::write_bytes_to_file(path, bytes);
+        assert!(res.is_ok());
+        assert_eq!(FileIO::read_file_to_bytes(path).unwrap(), bytes.to_owned());
+        std::fs::remove_file(path).unwrap();
+    }
+
     #[test]
     fn ut_getFileSizeWithInvalidFile_fails() {
         let res = FileIO::get_file_size(invalid_file);
-        assert_eq!(res, None);
+        assert!(matches!(res, Err(Error::Io { path, .. }) if path == invalid_file));
     }
 
     #[test]
     fn ut_fileToBytesWithInvalidFile_fails() {
         let res = FileIO::read_file_to_bytes(invalid_file);
-        assert_eq!(res, None);
+        assert!(res.is_err());
     }
 
     #[test]
@@ -149,6 +278,6 @@ mod fio_test {
             0,
             0
         );
-        assert_eq!(res, None);
+        assert!(res.is_err());
     }
 }
@@ -1,9 +1,9 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
 #![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
 #![allow(arithmetic_overflow)]
 
-use crate::constants::Val;
+use std::collections::VecDeque;
+use crate::traits::RollingHash;
 
 // largest 16-bit prime number
 const BASE: u32 = 65521;
@@ -45,8 +45,8 @@ pub struct Adler32 {
     pub s1: u32, 
     pub s2: u32,
     pub count: usize,
-    pub window: Vec<u8>,
-    pub rolled_out_byte: u8,
+    // ring buffer, rolling out is O(1)
+    window: VecDeque<u8>,
 }
 
 impl Adler32 {
@@ -56,20 +56,10 @@ impl Adler32 {
             s1: 1u32  & 0xFFFF,
             s2: 1u32 >> 16,
             count: 0,
-            rolled_out_byte: 0,
-            window: Vec::new(),
+            window: VecDeque::new(),
         }
     }
 
-    pub fn roll_in(&mut self, byte: u8) -> &mut Self {
-        self.s1 = (self.s1 + byte as u32) % BASE;
-        self.s2 = (self.s1 + self.s2) % BASE;
-        
-        self.window.push(byte);
-        self.count += 1;
-        self
-    }
-
     /// Convenience function initializing a context from the hash of a buffer.
     pub fn from_bytes(bytes: &[u8]) -> Self {
         let mut adler32 = Adler32::new();
@@ -77,28 +67,12 @@ impl Adler32 {
         adler32
     }
     
-    pub fn roll_out(& mut self) -> &mut Self {
-        let len = self.window.len() as u32;
-        if len == 0 {
-            self.count = 0;
-            return self;
-        }
-        self.rolled_out_byte = self.window.remove(0);
-
-        let rolled_out_byte = self.rolled_out_byte as u32;
-
-        self.s1 = (self.s1 + BASE - rolled_out_byte) % BASE;
-        self.s2 = ((self.s2 + BASE - 1).wrapping_add(BASE.wrapping_sub(len).wrapping_mul(rolled_out_byte))) % BASE;
-        
-        self.count -= 1;
-        self
-    }
-
     pub fn write_bytes(&mut self, bytes: &[u8]) -> &mut Self {
         let len = bytes.len();
          // in case user likes doing a byte at a time, keep it fas
         if len == 1 {
-            self.roll_in(bytes[0]);
+            self.s1 = (self.s1 + bytes[0] as u32) % BASE;
+            self.s2 = (self.s1 + self.s2) % BASE;
             self.count += 1;
             return self;
         }
@@ -157,12 +131,53 @@ impl Adler32 {
     pub fn sum32(&self) -> u32 {
         self.s2  << 16 | self.s1
     }
+}
+
+impl RollingHash for Adler32 {
+    fn roll_in(&mut self, byte: u8) {
+        self.s1 = (self.s1 + byte as u32) % BASE;
+        self.s2 = (self.s1 + self.s2) % BASE;
+
+        self.window.push_back(byte);
+        self.count += 1;
+    }
 
-    pub fn reset(&mut self) {
+    fn roll_out(&mut self) -> Option<u8> {
+        let len = self.window.len() as u32;
+        let rolled_out_byte = self.window.pop_front()?;
+        let byte = rolled_out_byte as u32;
+
+        self.s1 = (self.s1 + BASE - byte) % BASE;
+        // window may be longer than BASE
+        self.s2 = (self.s2 + BASE - 1 + (BASE - len % BASE) * byte) % BASE;
+
+        self.count -= 1;
+        Some(rolled_out_byte)
+    }
+
+    fn digest(&self) -> u32 {
+        self.sum32()
+    }
+
+    fn reset(&mut self) {
         self.s1 = 1u32  & 0xFFFF;
         self.s2 = 1u32 >> 16;
         self.count = 0;
-        self.window.drain(..);
+        self.window.clear();
+    }
+
+    fn window_len(&self) -> usize {
+        self.window.len()
+    }
+
+    fn window(&mut self) -> &[u8] {
+        self.window.make_contiguous()
+    }
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -170,6 +185,7 @@ impl Adler32 {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::test_utils::bytes;
     
     #[test]
     fn ut_testVectors_works() {
@@ -212,30 +228,42 @@ mod test {
         }
 
         assert_eq!(adler2.count, 4);
-        assert_eq!(adler2.window, b[..4]);
-        assert_ne!(adler1.window, adler2.window);
+        assert_eq!(adler2.window(), &b[..4]);
         // hashes must not mach
         assert_ne!(adler1.sum32(), adler2.sum32());
         // -------------- rolling --------------
         // roll window by 1 step 
-        adler2.roll_out();
-
-        let mut c = adler2.rolled_out_byte;
-        
-        assert_eq!(c, b[0]);
+        assert_eq!(adler2.roll_out(), Some(b[0]));
         assert_eq!(adler2.count, 3);
-        assert_eq!(adler2.window, b[1..4]);
+        assert_eq!(adler2.window(), &b[1..4]);
 
         adler2.roll_in(b[4]);
 
         assert_eq!(adler2.count, 4);
-        assert_eq!(adler2.window, b[1..5]);
+        assert_eq!(adler2.window_len(), 4);
+        assert_eq!(adler2.window(), &b[1..5]);
         // now hashes must mach
-        assert_eq!(adler2.sum32(), adler2.sum32());
-        // ----------------------------------------
-        
-        assert_eq!(adler2.window, b[1..5]);
+        assert_eq!(adler2.digest(), Adler32::from_bytes(&b[1..5]).sum32());
         // ----------------------------------------
+        adler2.reset();
+        assert_eq!(adler2.roll_out(), None);
+        assert_eq!(adler2, Adler32::new());
+    }
 
+    #[test]
+    fn ut_rollingLongWindow_works() {
+        // window longer than BASE, chunk sizes go past it
+        let len = BASE as usize + 4479;
+        let data = bytes(len + 64, 3);
+
+        let mut adler = Adler32::new();
+        for c in &data[..len] {
+            adler.roll_in(*c);
+        }
+        for i in 0..64 {
+            adler.roll_out();
+            adler.roll_in(data[len + i]);
+            assert_eq!(adler.digest(), Adler32::from_bytes(&data[i + 1..len + i + 1]).sum32());
+        }
     }
-}
\ No newline at end of file
+}
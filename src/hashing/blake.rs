@@ -0,0 +1,91 @@
+#![allow(non_snake_case)]
+
+use crate::traits::StrongHash;
+
+pub struct Blake3 {
+    hasher: blake3::Hasher,
+}
+
+impl Blake3 {
+    pub const LEN: usize = 32;
+
+    /// Creates an empty ctx for hashing a stream piece by piece.
+    pub fn new() -> Self {
+        Self {
+            hasher: blake3::Hasher::new(),
+        }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.hasher.update(bytes);
+        self
+    }
+
+    pub fn digest(&self) -> [u8; 32] {
+        *self.hasher.finalize().as_bytes()
+    }
+
+    pub fn sum256(bytes: &[u8]) -> [u8; 32] {
+        *blake3::hash(bytes).as_bytes()
+    }
+
+    // keyed mode, seed makes up the key
+    pub fn keyed_sum256(bytes: &[u8], seed: u64) -> [u8; 32] {
+        let mut key = [0u8; 32];
+        key[..8].copy_from_slice(&seed.to_le_bytes());
+        *blake3::keyed_hash(&key, bytes).as_bytes()
+    }
+}
+
+impl StrongHash for Blake3 {
+    const DIGEST_LEN: usize = Self::LEN;
+
+    fn sum_into(bytes: &[u8], seed: u64, out: &mut [u8]) {
+        let sum = match seed {
+            0 => Self::sum256(bytes),
+            seed => Self::keyed_sum256(bytes, seed),
+        };
+        out.copy_from_slice(&sum[..out.len()]);
+    }
+}
+
+impl Default for Blake3 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod blake_test {
+    use super::*;
+
+    #[test]
+    fn ut_sum256_works() {
+        let sum = Blake3::sum256(b"");
+        assert_eq!(sum.len(), Blake3::LEN);
+        assert_eq!(sum[..4], [0xaf, 0x13, 0x49, 0xb9]);
+        assert_ne!(Blake3::sum256(b"a"), sum);
+    }
+
+    #[test]
+    fn ut_seededSum_works() {
+        let mut plain = [0u8; 32];
+        let mut seeded = [0u8; 32];
+        Blake3::sum_into(b"chunk", 0, &mut plain);
+        Blake3::sum_into(b"chunk", 7, &mut seeded);
+        assert_eq!(plain, Blake3::sum256(b"chunk"));
+        assert_eq!(seeded, Blake3::keyed_sum256(b"chunk", 7));
+        assert_ne!(plain, seeded);
+    }
+
+    #[test]
+    fn ut_streaming_works() {
+        let mut blake = Blake3::new();
+        blake.write_bytes(b"sample data ").write_bytes(b"for rolling hash diff.");
+        assert_eq!(
+            blake.digest(),
+            Blake3::sum256(b"sample data for rolling hash diff.")
+        );
+    }
+}
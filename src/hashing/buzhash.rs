@@ -0,0 +1,119 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Cyclic polynomial (Buzhash) rolling hash:
+
+        hash = rotl(T[b[0]], n-1) ^ rotl(T[b[1]], n-2) ^ ... ^ T[b[n-1]]
+
+    T maps every byte value to a random 32-bit value.
+    no multiplications, only rotations and xors.
+*/
+
+use std::collections::VecDeque;
+use crate::traits::RollingHash;
+use super::gear::GEAR;
+
+// high halves of gear table values, random enough and already at hand
+const TABLE: [u32; 256] = table();
+
+const fn table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = (GEAR[i] >> 32) as u32;
+        i += 1;
+    }
+    table
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Buzhash {
+    pub hash: u32,
+    window: VecDeque<u8>,
+}
+
+impl Buzhash {
+    pub fn new() -> Self {
+        Self {
+            hash: 0,
+            window: VecDeque::new(),
+        }
+    }
+
+    /// Hash of a whole buffer, window is left empty.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut buz = Self::new();
+        for byte in bytes.iter() {
+            buz.hash = buz.hash.rotate_left(1) ^ TABLE[*byte as usize];
+        }
+        buz
+    }
+}
+
+impl RollingHash for Buzhash {
+    fn roll_in(&mut self, byte: u8) {
+        self.hash = self.hash.rotate_left(1) ^ TABLE[byte as usize];
+        self.window.push_back(byte);
+    }
+
+    fn roll_out(&mut self) -> Option<u8> {
+        let byte = self.window.pop_front()?;
+        // oldest byte got rotated once per byte after it
+        let n = (self.window.len() % 32) as u32;
+        self.hash ^= TABLE[byte as usize].rotate_left(n);
+        Some(byte)
+    }
+
+    fn digest(&self) -> u32 {
+        self.hash
+    }
+
+    fn reset(&mut self) {
+        self.hash = 0;
+        self.window.clear();
+    }
+
+    fn window_len(&self) -> usize {
+        self.window.len()
+    }
+
+    fn window(&mut self) -> &[u8] {
+        self.window.make_contiguous()
+    }
+}
+
+impl Default for Buzhash {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod buzhash_test {
+    use super::*;
+
+    #[test]
+    fn ut_rolling_works() {
+        const b: &[u8] = "@the Quick brown fox jumped over the lazy dog!".as_bytes();
+        // window longer than 32 makes rotations wrap around
+        let data = b.repeat(3);
+        let w = 40;
+        let mut buz = Buzhash::new();
+        for byte in &data[..w] {
+            buz.roll_in(*byte);
+        }
+        for i in w..data.len() {
+            assert_eq!(buz.roll_out(), Some(data[i - w]));
+            buz.roll_in(data[i]);
+            assert_eq!(buz.digest(), Buzhash::from_bytes(&data[i + 1 - w..=i]).digest());
+        }
+        assert_eq!(buz.window_len(), w);
+        assert_eq!(buz.window(), &data[data.len() - w..]);
+
+        buz.reset();
+        assert_eq!(buz.roll_out(), None);
+        assert_eq!(buz, Buzhash::new());
+    }
+}
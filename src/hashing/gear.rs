@@ -0,0 +1,78 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Gear rolling hash, as used by FastCDC for finding chunk boundaries.
+    each byte shifts hash left by one and adds a random value of that byte,
+    so the top bits of hash depend on last 64 bytes only.
+*/
+
+// random value per byte value, made by splitmix64 so it's same everywhere
+pub const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0u64;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Gear {
+    pub hash: u64,
+}
+
+impl Gear {
+    pub fn new() -> Self {
+        Self {
+            hash: 0,
+        }
+    }
+
+    pub fn roll_in(&mut self, byte: u8) -> &mut Self {
+        self.hash = (self.hash << 1).wrapping_add(GEAR[byte as usize]);
+        self
+    }
+
+    pub fn reset(&mut self) {
+        self.hash = 0;
+    }
+}
+
+impl Default for Gear {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod gear_test {
+    use super::*;
+
+    #[test]
+    fn ut_rollIn_works() {
+        let mut gear = Gear::new();
+        gear.roll_in(1).roll_in(2);
+        assert_eq!(gear.hash, (GEAR[1] << 1).wrapping_add(GEAR[2]));
+
+        // bytes older than 64 no longer matter
+        let mut other = Gear::new();
+        for byte in 0..64u8 {
+            gear.roll_in(byte);
+            other.roll_in(byte);
+        }
+        assert_eq!(gear.hash, other.hash);
+
+        gear.reset();
+        assert_eq!(gear, Gear::new());
+    }
+}
@@ -1,2 +1,191 @@
+pub mod blake;
 pub mod x2hash;
-pub mod adler;
\ No newline at end of file
+pub mod adler;
+pub mod gear;
+pub mod buzhash;
+pub mod rabin_karp;
+pub mod sha256;
+
+use crate::constants::Val;
+use crate::traits::StrongHash;
+use adler::Adler32;
+use blake::Blake3;
+use sha256::Sha256;
+use buzhash::Buzhash;
+use rabin_karp::RabinKarp;
+use x2hash::{X2Hash64, X2Hash128};
+
+/// Strong (L2) hash of a chunk, possibly truncated to strong_len bytes.
+/// unused tail bytes are zero.
+pub type StrongSum = [u8; Val::MAX_STRONG_LEN];
+
+/// Rolling hash used as weak (L1) hash, recorded in signature file.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WeakHash {
+    #[default]
+    Adler32,
+    RabinKarp,
+    Buzhash,
+}
+
+impl WeakHash {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "adler32" => Some(Self::Adler32),
+            "rabin-karp" => Some(Self::RabinKarp),
+            "buzhash" => Some(Self::Buzhash),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Adler32 => "adler32",
+            Self::RabinKarp => "rabin-karp",
+            Self::Buzhash => "buzhash",
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            Val::WEAK_HASH_ADLER32 => Some(Self::Adler32),
+            Val::WEAK_HASH_RABIN_KARP => Some(Self::RabinKarp),
+            Val::WEAK_HASH_BUZHASH => Some(Self::Buzhash),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Adler32 => Val::WEAK_HASH_ADLER32,
+            Self::RabinKarp => Val::WEAK_HASH_RABIN_KARP,
+            Self::Buzhash => Val::WEAK_HASH_BUZHASH,
+        }
+    }
+
+    // same value a rolling window over bytes ends up with
+    pub fn sum32(&self, bytes: &[u8]) -> u32 {
+        match self {
+            Self::Adler32 => Adler32::from_bytes(bytes).sum32(),
+            Self::RabinKarp => RabinKarp::from_bytes(bytes).hash,
+            Self::Buzhash => Buzhash::from_bytes(bytes).hash,
+        }
+    }
+}
+
+/// Hash used as strong (L2) hash, recorded in signature and delta files.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StrongAlgorithm {
+    #[default]
+    Xxh3_64,
+    Xxh3_128,
+    Blake3,
+    Sha256,
+}
+
+impl StrongAlgorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "xxh3-64" => Some(Self::Xxh3_64),
+            "xxh3-128" => Some(Self::Xxh3_128),
+            "blake3" => Some(Self::Blake3),
+            "sha256" => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Xxh3_64 => "xxh3-64",
+            Self::Xxh3_128 => "xxh3-128",
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            Val::STRONG_HASH_XXH3_64 => Some(Self::Xxh3_64),
+            Val::STRONG_HASH_XXH3_128 => Some(Self::Xxh3_128),
+            Val::STRONG_HASH_BLAKE3 => Some(Self::Blake3),
+            Val::STRONG_HASH_SHA256 => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::Xxh3_64 => Val::STRONG_HASH_XXH3_64,
+            Self::Xxh3_128 => Val::STRONG_HASH_XXH3_128,
+            Self::Blake3 => Val::STRONG_HASH_BLAKE3,
+            Self::Sha256 => Val::STRONG_HASH_SHA256,
+        }
+    }
+
+    // full digest length in bytes
+    pub fn digest_len(&self) -> usize {
+        match self {
+            Self::Xxh3_64 => X2Hash64::DIGEST_LEN,
+            Self::Xxh3_128 => X2Hash128::DIGEST_LEN,
+            Self::Blake3 => Blake3::DIGEST_LEN,
+            Self::Sha256 => Sha256::DIGEST_LEN,
+        }
+    }
+
+    // truncated lengths shrink signature at the cost of more collisions
+    pub fn check_len(&self, len: usize) -> bool {
+        (Val::MIN_STRONG_LEN..=self.digest_len()).contains(&len)
+    }
+
+    // digest of bytes truncated to len bytes,
+    // same seed must be used on both ends of a signature
+    pub fn sum(&self, bytes: &[u8], len: usize, seed: u64) -> StrongSum {
+        let mut sum = [0u8; Val::MAX_STRONG_LEN];
+        let out = &mut sum[..len];
+        match self {
+            Self::Xxh3_64 => X2Hash64::sum_into(bytes, seed, out),
+            Self::Xxh3_128 => X2Hash128::sum_into(bytes, seed, out),
+            Self::Blake3 => Blake3::sum_into(bytes, seed, out),
+            Self::Sha256 => Sha256::sum_into(bytes, seed, out),
+        }
+        sum
+    }
+}
+
+
+#[cfg(test)]
+mod hashing_test {
+    use super::*;
+
+    #[test]
+    fn ut_strongSum_works() {
+        let algos = [
+            StrongAlgorithm::Xxh3_64,
+            StrongAlgorithm::Xxh3_128,
+            StrongAlgorithm::Blake3,
+            StrongAlgorithm::Sha256,
+        ];
+        for algo in algos {
+            assert_eq!(StrongAlgorithm::from_id(algo.id()), Some(algo));
+            assert_eq!(StrongAlgorithm::from_name(algo.name()), Some(algo));
+
+            let full = algo.sum(b"chunk", algo.digest_len(), 0);
+            let cut = algo.sum(b"chunk", 4, 0);
+            assert_eq!(full[..4], cut[..4]);
+            assert!(cut[4..].iter().all(|b| *b == 0));
+            assert_ne!(full, algo.sum(b"chunx", algo.digest_len(), 0));
+            // other seed, other digest, same one every time
+            let seeded = algo.sum(b"chunk", algo.digest_len(), 42);
+            assert_ne!(full, seeded);
+            assert_eq!(seeded, algo.sum(b"chunk", algo.digest_len(), 42));
+        }
+        assert_eq!(
+            StrongAlgorithm::Xxh3_64.sum(b"chunk", 8, 0)[..8],
+            8438847523455501592u64.to_le_bytes()
+        );
+        assert!(StrongAlgorithm::Xxh3_64.check_len(8));
+        assert!(!StrongAlgorithm::Xxh3_64.check_len(9));
+        assert!(StrongAlgorithm::Sha256.check_len(32));
+        assert!(!StrongAlgorithm::Sha256.check_len(1));
+    }
+}
@@ -0,0 +1,135 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Polynomial (Rabin-Karp) rolling hash modulo 2^32:
+
+        hash = (b[0]+1)*P^(n-1) + (b[1]+1)*P^(n-2) + ... + (b[n-1]+1)
+
+    bytes are offset by one so leading zero bytes still count.
+    P is odd, hence invertible modulo 2^32, which lets P^(n-1)
+    be tracked as window shrinks without any division.
+*/
+
+use std::collections::VecDeque;
+use crate::traits::RollingHash;
+
+// FNV-1 32-bit prime
+const P: u32 = 0x01000193;
+const P_INV: u32 = inverse(P);
+
+// newton iteration for inverse modulo 2^32, each step doubles correct bits
+const fn inverse(p: u32) -> u32 {
+    let mut inv = p;
+    let mut i = 0;
+    while i < 5 {
+        inv = inv.wrapping_mul(2u32.wrapping_sub(p.wrapping_mul(inv)));
+        i += 1;
+    }
+    inv
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RabinKarp {
+    pub hash: u32,
+    // P^(bytes hashed)
+    pow: u32,
+    window: VecDeque<u8>,
+}
+
+impl RabinKarp {
+    pub fn new() -> Self {
+        Self {
+            hash: 0,
+            pow: 1,
+            window: VecDeque::new(),
+        }
+    }
+
+    /// Hash of a whole buffer, window is left empty.
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut rk = Self::new();
+        for byte in bytes.iter() {
+            rk.push(*byte);
+        }
+        rk
+    }
+
+    fn push(&mut self, byte: u8) {
+        self.hash = self.hash.wrapping_mul(P).wrapping_add(byte as u32 + 1);
+        self.pow = self.pow.wrapping_mul(P);
+    }
+}
+
+impl RollingHash for RabinKarp {
+    fn roll_in(&mut self, byte: u8) {
+        self.push(byte);
+        self.window.push_back(byte);
+    }
+
+    fn roll_out(&mut self) -> Option<u8> {
+        let byte = self.window.pop_front()?;
+        // oldest byte is weighted P^(n-1)
+        self.pow = self.pow.wrapping_mul(P_INV);
+        self.hash = self.hash.wrapping_sub((byte as u32 + 1).wrapping_mul(self.pow));
+        Some(byte)
+    }
+
+    fn digest(&self) -> u32 {
+        self.hash
+    }
+
+    fn reset(&mut self) {
+        self.hash = 0;
+        self.pow = 1;
+        self.window.clear();
+    }
+
+    fn window_len(&self) -> usize {
+        self.window.len()
+    }
+
+    fn window(&mut self) -> &[u8] {
+        self.window.make_contiguous()
+    }
+}
+
+impl Default for RabinKarp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod rabin_karp_test {
+    use super::*;
+
+    #[test]
+    fn ut_inverse_works() {
+        assert_eq!(P.wrapping_mul(P_INV), 1);
+    }
+
+    #[test]
+    fn ut_rolling_works() {
+        const b: &[u8] = "@the Quick brown fox jumped over the lazy dog!".as_bytes();
+        let mut rk = RabinKarp::new();
+        for byte in &b[..8] {
+            rk.roll_in(*byte);
+        }
+        for i in 8..b.len() {
+            assert_eq!(rk.roll_out(), Some(b[i - 8]));
+            rk.roll_in(b[i]);
+            assert_eq!(rk.digest(), RabinKarp::from_bytes(&b[i - 7..=i]).digest());
+            assert_eq!(rk.window(), &b[i - 7..=i]);
+        }
+        assert_eq!(rk.window_len(), 8);
+
+        // zero bytes in front make a difference
+        assert_ne!(RabinKarp::from_bytes(&[0, 1]).digest(), RabinKarp::from_bytes(&[1]).digest());
+
+        rk.reset();
+        assert_eq!(rk.roll_out(), None);
+        assert_eq!(rk, RabinKarp::new());
+    }
+}
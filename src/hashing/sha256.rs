@@ -0,0 +1,53 @@
+#![allow(non_snake_case)]
+
+use sha2::{Sha256 as Sha256Hasher, Digest};
+use crate::traits::StrongHash;
+
+pub struct Sha256;
+
+impl Sha256 {
+    pub fn sum256(bytes: &[u8]) -> [u8; 32] {
+        Sha256Hasher::digest(bytes).into()
+    }
+
+    // seed is hashed in front of bytes
+    pub fn seeded_sum256(bytes: &[u8], seed: u64) -> [u8; 32] {
+        Sha256Hasher::new()
+            .chain_update(seed.to_le_bytes())
+            .chain_update(bytes)
+            .finalize()
+            .into()
+    }
+}
+
+impl StrongHash for Sha256 {
+    const DIGEST_LEN: usize = 32;
+
+    fn sum_into(bytes: &[u8], seed: u64, out: &mut [u8]) {
+        let sum = match seed {
+            0 => Self::sum256(bytes),
+            seed => Self::seeded_sum256(bytes, seed),
+        };
+        out.copy_from_slice(&sum[..out.len()]);
+    }
+}
+
+
+#[cfg(test)]
+mod sha256_test {
+    use super::*;
+
+    #[test]
+    fn ut_sum256_works() {
+        let sum = Sha256::sum256(b"abc");
+        assert_eq!(sum[..4], [0xba, 0x78, 0x16, 0xbf]);
+
+        let mut out = [0u8; 4];
+        Sha256::sum_into(b"abc", 0, &mut out);
+        assert_eq!(out, sum[..4]);
+
+        Sha256::sum_into(b"abc", 7, &mut out);
+        assert_ne!(out, sum[..4]);
+        assert_eq!(out, Sha256::seeded_sum256(b"abc", 7)[..4]);
+    }
+}
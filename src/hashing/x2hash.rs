@@ -1,15 +1,38 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
 #![allow(non_snake_case)]
 
-use xxh3::hash64_with_seed;
-
-const SEED: u64 = 0;
+use xxh3::{hash64_with_seed, hash128_with_seed};
+use crate::traits::StrongHash;
 
 pub struct X2Hash64;
 
 impl X2Hash64 {
-    pub fn sum64(bytes: &[u8]) -> u64 {
-        hash64_with_seed(bytes, SEED)
+    pub fn sum64(bytes: &[u8], seed: u64) -> u64 {
+        hash64_with_seed(bytes, seed)
+    }
+}
+
+impl StrongHash for X2Hash64 {
+    const DIGEST_LEN: usize = 8;
+
+    fn sum_into(bytes: &[u8], seed: u64, out: &mut [u8]) {
+        let sum = Self::sum64(bytes, seed).to_le_bytes();
+        out.copy_from_slice(&sum[..out.len()]);
+    }
+}
+
+pub struct X2Hash128;
+
+impl X2Hash128 {
+    pub fn sum128(bytes: &[u8], seed: u64) -> u128 {
+        hash128_with_seed(bytes, seed)
+    }
+}
+
+impl StrongHash for X2Hash128 {
+    const DIGEST_LEN: usize = 16;
+
+    fn sum_into(bytes: &[u8], seed: u64, out: &mut [u8]) {
+        let sum = Self::sum128(bytes, seed).to_le_bytes();
+        out.copy_from_slice(&sum[..out.len()]);
     }
-}
\ No newline at end of file
+}
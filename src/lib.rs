@@ -0,0 +1,574 @@
+//! Rolling hash based file diff, in the spirit of rsync/librsync.
+//!
+//! Syncing a file takes three steps, which may run on different hosts:
+//!
+//! 1. [`signature`] hashes chunks of the original (base) file,
+//! 2. [`delta`] matches a modified file against that signature,
+//! 3. [`patch`] applies the delta to the base to rebuild the modified file.
+//!
+//! [`Signature`] and [`DeltaFile`] can be written to and read back from disk,
+//! see their `encode`/`decode` functions.
+
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![cfg_attr(test, allow(unused_mut, unused_variables))]
+#![cfg_attr(test, allow(clippy::get_first, clippy::needless_borrow, clippy::bool_assert_comparison))]
+
+mod fio;
+mod utils;
+mod traits;
+mod constants;
+pub mod error;
+pub mod model;
+pub mod hashing;
+#[cfg(test)]
+mod test_utils;
+
+use std::io::{Read, BufRead, Write};
+
+pub use error::{Error, Result};
+pub use model::patch::Patcher;
+pub use model::changes::Change;
+pub use model::chunker::Chunker;
+pub use hashing::{WeakHash, StrongAlgorithm};
+pub use model::delta::DiffingDelta;
+pub use model::signature::Signature;
+pub use model::delta_file::DeltaFile;
+pub use model::unified::UnifiedDiff;
+pub use model::report::{DeltaReport, Phases};
+pub use constants::Exit;
+
+// used by the binary, not part of the API
+#[doc(hidden)]
+pub use fio::{FileIO, Input, MapMode};
+#[doc(hidden)]
+pub use constants::Val;
+
+use utils::Utility;
+
+/// Options for computing a signature.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SignOptions {
+    /// size of chunks original file is cut into, average one for content defined chunks,
+    /// None picks one from size of original, see [`auto_chunk_size`];
+    /// [`signature`] of a stream needs one
+    pub chunk_size: Option<usize>,
+    /// fixed size or content defined chunks
+    pub chunker: Chunker,
+    /// rolling hash for finding chunks in modified file
+    pub weak_hash: WeakHash,
+    /// hash confirming weak hash matches
+    pub strong_hash: StrongAlgorithm,
+    /// bytes of strong hash kept per chunk, None keeps whole digest
+    pub strong_len: Option<usize>,
+    /// strong hash seed, None picks a random one
+    pub seed: Option<u64>,
+    /// threads hashing chunks, signature doesn't depend on it
+    pub threads: usize,
+}
+
+impl SignOptions {
+    pub fn new() -> Self {
+        Self {
+            chunk_size: None,
+            chunker: Chunker::Fixed,
+            weak_hash: WeakHash::Adler32,
+            strong_hash: StrongAlgorithm::Xxh3_64,
+            strong_len: None,
+            seed: None,
+            threads: 1,
+        }
+    }
+}
+
+impl SignOptions {
+    // chunk size given, or the automatic one for original of f_size bytes
+    fn chunk_size_for(&self, f_size: usize) -> usize {
+        self.chunk_size.unwrap_or_else(|| Utility::auto_chunk_size(f_size))
+    }
+}
+
+impl Default for SignOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Chunk size picked for an original of `f_size` bytes when none is given,
+/// square root of the size like rsync does, within 700 bytes and 128 KiB.
+pub fn auto_chunk_size(f_size: usize) -> usize {
+    Utility::auto_chunk_size(f_size)
+}
+
+/// Computes signature of the original (base) stream.
+///
+/// Stream size is not known up front, so `chunk_size` must be given,
+/// [`signature_from_bytes`] picks one when it isn't.
+pub fn signature<R: Read>(reader: R, opts: &SignOptions) -> Result<Signature> {
+    let c_size = opts.chunk_size.ok_or_else(|| Error::InvalidChunkSize(
+        "stream size isn't known up front, chunk size must be given".to_owned()
+    ))?;
+    let mut sign = Signature::with_chunker(opts.chunker);
+    sign.weak = opts.weak_hash;
+    sign.set_strong(opts.strong_hash, opts.strong_len)?;
+    sign.seed = opts.seed.unwrap_or_else(Utility::random_seed);
+    sign.reader_to_sign_list_threaded(reader, c_size, opts.threads)?;
+    Ok(sign)
+}
+
+/// Same as [`signature`] for an original already in memory, e.g. memory mapped.
+///
+/// Chunks are cut in place, nothing is copied.
+pub fn signature_from_bytes(bytes: &[u8], opts: &SignOptions) -> Result<Signature> {
+    let mut sign = Signature::with_chunker(opts.chunker);
+    sign.weak = opts.weak_hash;
+    sign.set_strong(opts.strong_hash, opts.strong_len)?;
+    sign.seed = opts.seed.unwrap_or_else(Utility::random_seed);
+    sign.bytes_to_sign_list(bytes, opts.chunk_size_for(bytes.len()), opts.threads)?;
+    Ok(sign)
+}
+
+/// Computes delta turning the file behind `sign` into the modified stream.
+pub fn delta<R: BufRead>(sign: &Signature, reader: R) -> Result<DeltaFile> {
+    delta_threaded(sign, reader, 1)
+}
+
+/// Same as [`delta`] with the search spread over `threads` threads.
+///
+/// The modified stream is searched in segments at once and the results are
+/// stitched together, the delta is the same as the one of [`delta`].
+pub fn delta_threaded<R: BufRead>(sign: &Signature, reader: R, threads: usize) -> Result<DeltaFile> {
+    let mut diff_delta = DiffingDelta::new(sign);
+    diff_delta.reader_to_delta_list_threaded(reader, sign.c_size, threads)?;
+    Ok(DeltaFile::from_delta(&diff_delta))
+}
+
+/// Same as [`delta_threaded`] for a modified file already in memory, e.g. memory mapped.
+///
+/// It is searched in place, only literal bytes are copied into the delta.
+pub fn delta_from_bytes(sign: &Signature, bytes: &[u8], threads: usize) -> Result<DeltaFile> {
+    let mut diff_delta = DiffingDelta::new(sign);
+    diff_delta.bytes_to_delta_list(bytes, sign.c_size, threads)?;
+    Ok(DeltaFile::from_delta(&diff_delta))
+}
+
+/// Applies delta to base bytes and writes the rebuilt file into writer.
+///
+/// Fails with [`Error::ChecksumMismatch`] if base is not the file the delta
+/// was made against, or if the rebuilt file doesn't match; nothing is written then.
+pub fn patch<W: Write>(base: &[u8], delta: &DeltaFile, mut writer: W) -> Result<()> {
+    writer.write_all(&delta.apply(base)?)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Computes the inverse of `delta`, turning the patched file back into base.
+///
+/// Keep it next to the forward delta to roll an update back
+/// without keeping a copy of the old file.
+pub fn reverse(base: &[u8], delta: &DeltaFile) -> Result<DeltaFile> {
+    let patched = delta.apply(base)?;
+    delta.reverse(base, &patched)
+}
+
+#[cfg(test)]
+mod lib_test {
+    use super::*;
+    use crate::fio::FileIO;
+    use crate::model::changes::Change;
+    use crate::hashing::adler::Adler32;
+    use crate::hashing::x2hash::X2Hash64;
+    use crate::test_utils::bytes;
+    
+    const chunk_size: usize = 4;
+    const original_size: usize = 34;
+    const original: &str = "./test/files/original.dat";
+    const added_chars: &str = "./test/files/added_chars.dat";
+    const removed_chars: &str = "./test/files/removed_chars.dat";
+
+    fn round_trip(modified: &str) -> (Vec<u8>, Vec<u8>) {
+        let mut sign = Signature::new();
+        sign.file_to_sign_list(original, chunk_size, original_size).unwrap();
+
+        let mut delta = DiffingDelta::new(&sign);
+        delta.file_to_delta_list(modified, chunk_size).unwrap();
+
+        let patcher = Patcher::new(&delta.list);
+        (
+            patcher.apply(&FileIO::read_file_to_bytes(original).unwrap()).unwrap(),
+            FileIO::read_file_to_bytes(modified).unwrap()
+        )
+    }
+
+    #[test]
+    fn ut_createSignList_works() {
+        let mut sign = Signature::new();
+        let res = sign.file_to_sign_list(
+            original,
+            chunk_size,
+            original_size
+        );
+
+        assert!(res.is_ok());
+        assert_eq!(
+            sign.list.len(), 
+            Utility::get_num_of_chunks(
+                original_size, 
+                chunk_size
+            )
+        );
+        
+        let mut adler = Adler32::new();
+        
+        let first_chunk = &FileIO::read_file_to_bytes(
+            original
+        ).unwrap()[0..chunk_size];
+        //  check L1 hash
+        assert_eq!(
+            sign.list.first().unwrap().L1, 
+            adler.write_bytes(first_chunk).sum32()
+        );
+        // check L2 hash
+        assert_eq!(
+            sign.list.first().unwrap().L2[..8], 
+            X2Hash64::sum64(first_chunk, sign.seed).to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn ut_createDeltaListWithAdditions_works() {
+        let mut sign = Signature::new();
+        let res = sign.file_to_sign_list(
+            original,
+            chunk_size,
+            original_size
+        );
+
+        assert!(res.is_ok());
+
+        let mut delta = DiffingDelta::new(&sign);
+
+        let res = delta.file_to_delta_list(
+            added_chars,
+            chunk_size
+        );
+
+        assert!(res.is_ok());
+
+        assert_eq!(delta.list, vec![
+            // "sample data " in one go
+            Change::copy(0, 12),
+            Change::literal("foradded ".as_bytes().to_owned()),
+            // "rolling hash dif"
+            Change::copy(16, 16),
+            Change::literal("f.at the end as well!!".as_bytes().to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn ut_createDeltaListWithRemovals_works() {
+        let mut sign = Signature::new();
+        let res = sign.file_to_sign_list(
+            original,
+            chunk_size,
+            original_size
+        );
+
+        assert!(res.is_ok());
+
+        let mut delta = DiffingDelta::new(&sign);
+
+        let res = delta.file_to_delta_list(
+            removed_chars,
+            chunk_size
+        );
+
+        assert!(res.is_ok());
+
+        // chunks at 0 and 20 in original are gone
+        assert_eq!(delta.list, vec![
+            Change::literal("sap".as_bytes().to_owned()),
+            Change::copy(4, 16),
+            Change::literal("i ".as_bytes().to_owned()),
+            Change::copy(24, 10),
+        ]);
+    }
+
+    #[test]
+    fn ut_patchWithAdditions_works() {
+        let (patched, modified) = round_trip(added_chars);
+        assert_eq!(patched, modified);
+    }
+
+    #[test]
+    fn ut_patchWithRemovals_works() {
+        let (patched, modified) = round_trip(removed_chars);
+        assert_eq!(patched, modified);
+    }
+
+    #[test]
+    fn ut_patchFromDecodedDeltaFile_works() {
+        let mut sign = Signature::new();
+        sign.file_to_sign_list(original, chunk_size, original_size).unwrap();
+
+        let mut delta = DiffingDelta::new(&sign);
+        delta.file_to_delta_list(added_chars, chunk_size).unwrap();
+
+        let bytes = DeltaFile::from_delta(&delta).encode();
+        let delta_file = DeltaFile::decode(&bytes).unwrap();
+
+        assert_eq!(delta_file.c_size, chunk_size);
+        assert_eq!(delta_file.original_size, original_size);
+        assert_eq!(delta_file.list, delta.list);
+
+        let patcher = Patcher::new(&delta_file.list);
+        assert_eq!(
+            patcher.apply(&FileIO::read_file_to_bytes(original).unwrap()).unwrap(),
+            FileIO::read_file_to_bytes(added_chars).unwrap()
+        );
+    }
+
+    #[test]
+    fn ut_deltaFromSignFile_works() {
+        let path = std::env::temp_dir().join("rhsync_lib_test.sig");
+        let path = path.to_str().unwrap();
+
+        let mut sign = Signature::new();
+        sign.file_to_sign_list(original, chunk_size, original_size).unwrap();
+        sign.write_to_file(path).unwrap();
+
+        // delta side only sees the signature file
+        let loaded = Signature::read_from_file(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, sign);
+
+        let c_size = loaded.c_size;
+        let mut delta = DiffingDelta::new(&loaded);
+        delta.file_to_delta_list(removed_chars, c_size).unwrap();
+
+        let patcher = Patcher::new(&delta.list);
+        assert_eq!(
+            patcher.apply(&FileIO::read_file_to_bytes(original).unwrap()).unwrap(),
+            FileIO::read_file_to_bytes(removed_chars).unwrap()
+        );
+    }
+
+    #[test]
+    fn ut_signatureDeltaPatchApi_works() {
+        let base = FileIO::read_file_to_bytes(original).unwrap();
+        let modified = FileIO::read_file_to_bytes(added_chars).unwrap();
+
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(chunk_size);
+        let sign = signature(&base[..], &opts).unwrap();
+        assert_eq!(sign.c_size, chunk_size);
+        assert_eq!(sign.f_size, base.len());
+
+        let delta_file = delta(&sign, &modified[..]).unwrap();
+        assert_eq!(delta_file.c_size, sign.c_size);
+
+        let mut patched = Vec::<u8>::new();
+        patch(&base, &delta_file, &mut patched).unwrap();
+        assert_eq!(patched, modified);
+    }
+
+    #[test]
+    fn ut_signatureDeltaPatchApiWithGear_works() {
+        let base = bytes(200_000, 5);
+        let mut modified = "inserted in front".as_bytes().to_owned();
+        modified.extend_from_slice(&base);
+
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(1024);
+        opts.chunker = Chunker::gear(1024);
+        let sign = signature(&base[..], &opts).unwrap();
+        assert_eq!(sign.chunker, opts.chunker);
+
+        let delta_file = delta(&sign, &modified[..]).unwrap();
+        // only chunks around the insert are sent as literals
+        let literal_bytes: usize = delta_file.list.iter()
+            .filter(|change| matches!(change, Change::Literal(_)))
+            .map(|change| change.len())
+            .sum();
+        assert!(literal_bytes < 3 * 4096, "{} literal bytes", literal_bytes);
+
+        let mut patched = Vec::<u8>::new();
+        patch(&base, &delta_file, &mut patched).unwrap();
+        assert_eq!(patched, modified);
+    }
+
+    #[test]
+    fn ut_signatureApiWithStrongHash_works() {
+        let base = FileIO::read_file_to_bytes(original).unwrap();
+        let modified = FileIO::read_file_to_bytes(removed_chars).unwrap();
+
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(chunk_size);
+        opts.strong_hash = StrongAlgorithm::Blake3;
+        opts.strong_len = Some(12);
+        let sign = signature(&base[..], &opts).unwrap();
+        assert_eq!((sign.strong, sign.strong_len), (StrongAlgorithm::Blake3, 12));
+
+        let delta_file = delta(&sign, &modified[..]).unwrap();
+        assert_eq!((delta_file.strong, delta_file.strong_len), (StrongAlgorithm::Blake3, 12));
+        let mut patched = Vec::<u8>::new();
+        patch(&base, &delta_file, &mut patched).unwrap();
+        assert_eq!(patched, modified);
+
+        opts.strong_len = Some(40);
+        assert!(matches!(signature(&base[..], &opts), Err(Error::Usage(_))));
+    }
+
+    #[test]
+    fn ut_signatureApiWithSeed_works() {
+        let base = FileIO::read_file_to_bytes(original).unwrap();
+        let modified = FileIO::read_file_to_bytes(added_chars).unwrap();
+
+        // random seed unless given
+        let first = signature_from_bytes(&base, &SignOptions::new()).unwrap();
+        let second = signature_from_bytes(&base, &SignOptions::new()).unwrap();
+        assert_ne!(first.seed, second.seed);
+        assert_ne!(first.list, second.list);
+
+        // given seed is reproducible
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(chunk_size);
+        opts.seed = Some(42);
+        let sign = signature(&base[..], &opts).unwrap();
+        assert_eq!(sign.encode(), signature(&base[..], &opts).unwrap().encode());
+
+        // seed is read back from signature file and used for delta
+        let sign = Signature::decode(&sign.encode()).unwrap();
+        assert_eq!(sign.seed, 42);
+        opts.threads = 4;
+        assert_eq!(signature(&base[..], &opts).unwrap(), sign);
+        let delta_file = delta(&sign, &modified[..]).unwrap();
+        assert!(delta_file.list.iter().any(|change| matches!(change, Change::Copy { .. })));
+        assert_eq!(delta_threaded(&sign, &modified[..], 3).unwrap(), delta_file);
+        assert_eq!(delta_from_bytes(&sign, &modified, 1).unwrap(), delta_file);
+        assert_eq!(signature_from_bytes(&base, &opts).unwrap(), sign);
+        let mut patched = Vec::new();
+        patch(&base, &delta_file, &mut patched).unwrap();
+        assert_eq!(patched, modified);
+    }
+
+    #[test]
+    fn ut_signatureApiWithAutoChunkSize_works() {
+        let base: Vec<u8> = (0..3_000_000u32).map(|i| (i.wrapping_mul(2654435761) >> 11) as u8).collect();
+        let mut modified = base.clone();
+        modified[1_000_000] ^= 1;
+
+        // picked from size of original and recorded in both headers
+        let sign = signature_from_bytes(&base, &SignOptions::new()).unwrap();
+        assert_eq!(sign.c_size, Utility::auto_chunk_size(base.len()));
+        assert_eq!(sign.c_size, 1728);
+        assert_eq!(Signature::decode(&sign.encode()).unwrap().c_size, 1728);
+        let delta_file = delta_from_bytes(&sign, &modified, 1).unwrap();
+        assert_eq!(DeltaFile::decode(&delta_file.encode()).unwrap().c_size, 1728);
+        let mut patched = Vec::new();
+        patch(&base, &delta_file, &mut patched).unwrap();
+        assert_eq!(patched, modified);
+
+        // unknown stream size, none picked
+        assert!(matches!(
+            signature(&base[..], &SignOptions::new()),
+            Err(Error::InvalidChunkSize(_))
+        ));
+
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(4096);
+        assert_eq!(signature_from_bytes(&base, &opts).unwrap().c_size, 4096);
+    }
+
+    // every way of signing and diffing agrees and patches back
+    fn edge_case_delta(base: &[u8], modified: &[u8], opts: &SignOptions) -> Vec<Change> {
+        let sign = signature_from_bytes(base, opts).unwrap();
+        // stream takes chunk size picked for bytes
+        let stream_opts = SignOptions { chunk_size: Some(sign.c_size), ..opts.clone() };
+        assert_eq!(signature(base, &stream_opts).unwrap(), sign);
+        let sign = Signature::decode(&sign.encode()).unwrap();
+
+        let delta_file = delta(&sign, modified).unwrap();
+        assert_eq!(delta_threaded(&sign, modified, 3).unwrap(), delta_file);
+        assert_eq!(delta_from_bytes(&sign, modified, 1).unwrap(), delta_file);
+        let delta_file = DeltaFile::decode(&delta_file.encode()).unwrap();
+
+        let mut patched = Vec::new();
+        patch(base, &delta_file, &mut patched).unwrap();
+        assert_eq!(patched, modified);
+        let mut rolled_back = Vec::new();
+        patch(&patched, &reverse(base, &delta_file).unwrap(), &mut rolled_back).unwrap();
+        assert_eq!(rolled_back, base);
+        delta_file.list
+    }
+
+    #[test]
+    fn ut_emptyTinyAndIdenticalFiles_works() {
+        let text = FileIO::read_file_to_bytes(original).unwrap();
+        for chunker in [Chunker::Fixed, Chunker::gear(4)] {
+            let mut opts = SignOptions::new();
+            opts.chunk_size = Some(chunk_size);
+            opts.chunker = chunker;
+            opts.seed = Some(7);
+            let literal = |bytes: &[u8]| vec![Change::literal(bytes.to_owned())];
+
+            // empty original, empty modified or both
+            assert_eq!(edge_case_delta(b"", b"", &opts), vec![]);
+            assert_eq!(edge_case_delta(b"", b"abc", &opts), literal(b"abc"));
+            assert_eq!(edge_case_delta(b"abc", b"", &opts), vec![]);
+            assert_eq!(edge_case_delta(&text, b"", &opts), vec![]);
+            // smaller than one chunk
+            assert_eq!(edge_case_delta(b"abc", b"abc", &opts), vec![Change::copy(0, 3)]);
+            assert_eq!(edge_case_delta(b"abc", b"abd", &opts), literal(b"abd"));
+            assert_eq!(edge_case_delta(b"a", b"ab", &opts), literal(b"ab"));
+            // byte identical, one copy of everything
+            assert_eq!(edge_case_delta(&text, &text, &opts), vec![Change::copy(0, text.len())]);
+        }
+        // one chunk bigger than the whole file, the automatic way
+        let mut opts = SignOptions::new();
+        opts.seed = Some(7);
+        assert_eq!(edge_case_delta(&text, &text, &opts), vec![Change::copy(0, text.len())]);
+        assert_eq!(edge_case_delta(b"", b"", &opts), vec![]);
+    }
+
+    #[test]
+    fn ut_signatureApiWithZeroChunkSize_fails() {
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(0);
+        assert!(matches!(
+            signature("abc".as_bytes(), &opts),
+            Err(Error::InvalidChunkSize(_))
+        ));
+    }
+
+    #[test]
+    fn ut_patchApiWithWrongBase_fails() {
+        let base = FileIO::read_file_to_bytes(original).unwrap();
+        let modified = FileIO::read_file_to_bytes(added_chars).unwrap();
+
+        let sign = signature_from_bytes(&base, &SignOptions::new()).unwrap();
+        let delta_file = delta(&sign, &modified[..]).unwrap();
+        assert_eq!(delta_file.modified_size, modified.len());
+
+        let mut patched = Vec::<u8>::new();
+        assert!(matches!(
+            patch(&modified, &delta_file, &mut patched),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+        assert!(patched.is_empty());
+    }
+
+    #[test]
+    fn ut_reverseApi_works() {
+        let base = FileIO::read_file_to_bytes(original).unwrap();
+        let modified = FileIO::read_file_to_bytes(removed_chars).unwrap();
+
+        let sign = signature_from_bytes(&base, &SignOptions::new()).unwrap();
+        let forward = delta(&sign, &modified[..]).unwrap();
+        let backward = reverse(&base, &forward).unwrap();
+
+        let mut reverted = Vec::<u8>::new();
+        patch(&modified, &backward, &mut reverted).unwrap();
+        assert_eq!(reverted, base);
+    }
+}
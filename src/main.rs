@@ -1,172 +1,203 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
+#![allow(non_snake_case)]
 
-mod fio;
-mod utils;
-mod model;
-mod traits;
 mod common;
-mod hashing;
-mod constants;
 
-use model::delta::DiffingDelta;
-use model::signature::Signature;
-
-use crate::constants::{Val};
-use crate::common::{CmdArgs, };
+use std::io::{self, Write, ErrorKind};
+use rhsync_diff_tool::{
+    Exit,
+    Input,
+    FileIO,
+    Error,
+    Result,
+    DeltaFile,
+    Signature,
+    SignOptions,
+    UnifiedDiff,
+    DeltaReport,
+    Phases,
+    DiffingDelta,
+};
+
+use crate::common::{CmdArgs, Command, Format};
 
 fn main() {
     let mut args = CmdArgs::new();
-    if args.parse().is_none() {
-        return;
+    if let Err(e) = args.parse() {
+        eprintln!("Error! {}", e);
+        if let Error::Usage(_) = e {
+            eprintln!("{}", args.usage());
+        }
+        std::process::exit(e.exit_code());
+    }
+    if args.show_help {
+        println!("{}", args.usage());
+        std::process::exit(Exit::OK);
     }
 
-    let mut sign = Signature::new();
-
-    // ------------- sign list creation --------------
-    if sign.file_to_sign_list(
-        &args.original_file_path,
-        args.chunk_size,
-        args.original_file_size
-    ).is_none() { return; }
-
-    // ------------- delta list creation -------------
-    let mut diff_delta = DiffingDelta::new(&mut sign);
+    let res = match args.command {
+        Command::Signature => run_signature(&args),
+        Command::Delta => run_delta(&args),
+        Command::Patch => run_patch(&args),
+        Command::Diff => run_diff(&args),
+    };
+
+    if let Err(e) = res {
+        // reader of stdout is gone, e.g. output piped into head
+        if matches!(&e, Error::Io { source, .. } if source.kind() == ErrorKind::BrokenPipe) {
+            std::process::exit(Exit::OK);
+        }
+        eprintln!("Error! {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
 
-    if diff_delta.file_to_delta_list(
-        &args.modified_file_path,
-        args.chunk_size
-    ).is_none() { return; }
+fn sign_options(args: &CmdArgs) -> SignOptions {
+    let mut opts = SignOptions::new();
+    opts.chunk_size = args.chunk_size;
+    opts.chunker = args.chunker;
+    opts.weak_hash = args.weak_hash;
+    opts.strong_hash = args.strong_hash;
+    opts.strong_len = args.strong_len;
+    opts.seed = args.seed;
+    opts.threads = args.threads;
+    opts
+}
 
-    println!("delta list: {:#?}", diff_delta.list);
+// signature of original file, mapped into memory or streamed
+fn sign_file(args: &CmdArgs) -> Result<Signature> {
+    let opts = sign_options(args);
+    let sign = match FileIO::open_input(&args.original_file_path, args.map_mode)? {
+        Input::Mapped(map) => rhsync_diff_tool::signature_from_bytes(&map, &opts),
+        Input::Stream(reader) => rhsync_diff_tool::signature(reader, &opts),
+    };
+    sign.map_err(|e| e.with_path(&args.original_file_path))
 }
 
-#[cfg(test)]
-mod main_test {
-    use super::*;
-    use crate::fio::FileIO;
-    use crate::utils::Utility;
-    use crate::model::changes::Change;
-    use crate::hashing::adler::Adler32;
-    use crate::hashing::x2hash::X2Hash64;
-    
-    const chunk_size: usize = 4;
-    const original_size: usize = 34;
-    const original: &str = "./test/files/original.dat";
-    const added_chars: &str = "./test/files/added_chars.dat";
-    const removed_chars: &str = "./test/files/removed_chars.dat";
-
-    #[test]
-    fn ut_createSignList_works() {
-        let mut sign = Signature::new();
-        let res = sign.file_to_sign_list(
-            original,
-            chunk_size,
-            original_size
-        );
+fn run_signature(args: &CmdArgs) -> Result<()> {
+    let mut phases = Phases::new();
+    let sign = phases.time("signature", || sign_file(args))?;
+    phases.time("write", || sign.write_to_file(&args.signature_file_path))?;
 
-        assert_ne!(res, None);
-        assert_eq!(
-            sign.list.len(), 
-            Utility::get_num_of_chunks(
-                original_size, 
-                chunk_size
-            )
-        );
-        
-        let mut adler = Adler32::new();
-        
-        let first_chunk = &FileIO::read_file_to_bytes(
-            &original
-        ).unwrap()[0..chunk_size];
-        //  check L1 hash
-        assert_eq!(
-            sign.list.get(0).unwrap().L1, 
-            adler.write_bytes(first_chunk).sum32()
-        );
-        // check L2 hash
-        assert_eq!(
-            sign.list.get(0).unwrap().L2, 
-            X2Hash64::sum64(first_chunk)
-        );
+    if args.stats {
+        eprint!("{}{}", DeltaReport::sign_stats(&sign), phases);
     }
+    Ok(())
+}
 
-    #[test]
-    fn ut_createDeltaListWithAdditions_works() {
-        let mut sign = Signature::new();
-        let res = sign.file_to_sign_list(
-            &original,
-            chunk_size,
-            original_size
+fn run_delta(args: &CmdArgs) -> Result<()> {
+    let mut phases = Phases::new();
+    let sign = phases.time("read", || Signature::read_from_file(&args.signature_file_path))?;
+
+    // changes are written as they are found, delta is never held whole
+    let out = FileIO::create_file_writer(&args.delta_file_path)?;
+    let mut diff_delta = DiffingDelta::with_output(&sign, out);
+    phases.time("delta", || delta_list(args, &mut diff_delta))?;
+    let delta_size = phases.time("write", || diff_delta.finish())
+        .map_err(|e| e.with_path(&args.delta_file_path))?;
+
+    if args.stats {
+        let report = DeltaReport::new(
+            &diff_delta,
+            delta_size,
+            &args.original_file_path,
+            &args.modified_file_path
         );
+        eprint!("{}{}", report.stats(), phases);
+    }
+    Ok(())
+}
 
-        assert_ne!(res, None);
+// delta of modified file, mapped into memory or streamed
+fn delta_list(args: &CmdArgs, diff_delta: &mut DiffingDelta) -> Result<()> {
+    let res = match FileIO::open_input(&args.modified_file_path, args.map_mode)? {
+        Input::Mapped(map) => diff_delta.bytes_to_delta_list(&map, diff_delta.sign.c_size, args.threads),
+        Input::Stream(reader) => {
+            diff_delta.reader_to_delta_list_threaded(reader, diff_delta.sign.c_size, args.threads)
+        }
+    };
+    res.map_err(|e| e.with_path(&args.modified_file_path))
+}
 
-        let mut delta = DiffingDelta::new(&mut sign);
+fn run_patch(args: &CmdArgs) -> Result<()> {
+    let mut phases = Phases::new();
+    let delta_file = phases.time("read", || DeltaFile::read_from_file(&args.delta_file_path))?;
 
-        let res = delta.file_to_delta_list(
-            added_chars,
-            chunk_size
-        );
+    // original and result are both verified against delta checksums
+    phases.time("patch", || delta_file.file_to_patched_file(
+        &args.original_file_path,
+        args.patched_file_path.as_deref().unwrap_or_default(),
+        args.reverse_delta_file_path.as_deref()
+    ))?;
 
-        assert_ne!(res, None);
-
-        assert_eq!(delta.list.len(), 4);
-        
-        let ch0 = delta.get(0);
-        assert_ne!(ch0, None);
-        let ch0 = ch0.unwrap();
-        assert_eq!(ch0.del_chunk, false);
-        assert_eq!(ch0.before, Some(true));
-        assert_eq!(ch0.add_or_del_idx, 16);
-        assert_eq!(ch0.content, Some("foradded ".as_bytes().to_owned()));
+    if args.stats {
+        eprint!("{}{}", DeltaReport::patch_stats(&delta_file), phases);
     }
+    Ok(())
+}
 
-    #[test]
-    fn ut_createDeltaListWithRemovals_works() {
-        let mut sign = Signature::new();
-        let res = sign.file_to_sign_list(
-            &original,
-            chunk_size,
-            original_size
-        );
-
-        assert_ne!(res, None);
+fn run_diff(args: &CmdArgs) -> Result<()> {
+    let mut phases = Phases::new();
+    // ------------- sign list creation --------------
+    let sign = phases.time("signature", || sign_file(args))?;
 
-        let mut delta = DiffingDelta::new(&mut sign);
+    // ------------- delta list creation -------------
+    let mut diff_delta = DiffingDelta::new(&sign);
+    phases.time("delta", || delta_list(args, &mut diff_delta))?;
 
-        let res = delta.file_to_delta_list(
-            removed_chars,
-            chunk_size
-        );
+    let report = DeltaReport::new(
+        &diff_delta,
+        diff_delta.encoded_len(),
+        &args.original_file_path,
+        &args.modified_file_path
+    );
+    phases.time("output", || print_delta(args, &diff_delta, &report))?;
+
+    // ------------- patching (optional) -------------
+    if let Some(patched_file_path) = &args.patched_file_path {
+        let delta_file = DeltaFile::from_delta(&diff_delta);
+        phases.time("patch", || delta_file.file_to_patched_file(
+            &args.original_file_path,
+            patched_file_path,
+            args.reverse_delta_file_path.as_deref()
+        ))?;
+
+        // unified and json output stay parsable
+        if args.format == Format::Debug {
+            writeln!(io::stdout(), "patched file written to: {}", patched_file_path)?;
+        }
+    } else if let Some(reverse_path) = &args.reverse_delta_file_path {
+        let delta_file = DeltaFile::from_delta(&diff_delta);
+        phases.time("reverse", || {
+            let original = FileIO::read_file_to_bytes(&args.original_file_path)?;
+            let modified = FileIO::read_file_to_bytes(&args.modified_file_path)?;
+            delta_file.reverse(&original, &modified)?.write_to_file(reverse_path)
+        })?;
+    }
 
-        assert_ne!(res, None);
+    if args.stats {
+        eprint!("{}{}", report.stats(), phases);
+    }
+    Ok(())
+}
 
-        assert_eq!(delta.list.len(), 4);
-        
-        let mut chs = Vec::<Change>::new();
-        for ch in delta.list.iter() {
-            chs.push(ch.clone());
-        }
-        // delete chunk at 20 in original
-        assert_eq!(chs[3].del_chunk, true);
-        assert_eq!(chs[3].before, None);
-        assert_eq!(chs[3].add_or_del_idx, 20);
-        assert_eq!(chs[3].content, None);
-        // delete chunk at 0 in original
-        assert_eq!(chs[2].del_chunk, true);
-        assert_eq!(chs[2].before, None);
-        assert_eq!(chs[2].add_or_del_idx, 0);
-        assert_eq!(chs[2].content, None);
-        // add content before chunk at 24 in original
-        assert_eq!(chs[1].del_chunk, false);
-        assert_eq!(chs[1].before, Some(true));
-        assert_eq!(chs[1].add_or_del_idx, 24);
-        assert_eq!(chs[1].content, Some("i ".as_bytes().to_owned()));
-        // add content before chunk at 4 in original
-        assert_eq!(chs[0].del_chunk, false);
-        assert_eq!(chs[0].before, Some(true));
-        assert_eq!(chs[0].add_or_del_idx, 4);
-        assert_eq!(chs[0].content, Some("sap".as_bytes().to_owned()));
+// write errors are returned, not panicked on like print! does
+fn print_delta(args: &CmdArgs, diff_delta: &DiffingDelta, report: &DeltaReport) -> Result<()> {
+    let mut out = io::stdout().lock();
+    match args.format {
+        Format::Debug => {
+            writeln!(out, "delta list: {:#?}", diff_delta.list)?;
+            writeln!(out, "reused chunks: {:?}", diff_delta.reused_chunks())?;
+            writeln!(out, "deleted chunks: {:?}", diff_delta.deleted_chunks())?;
+        },
+        Format::Unified => {
+            let original = FileIO::read_file_to_bytes(&args.original_file_path)?;
+            write!(out, "{}", UnifiedDiff::new(&original, &diff_delta.list)?.render(
+                &args.original_file_path,
+                &args.modified_file_path
+            ))?;
+        },
+        Format::Json => writeln!(out, "{}", report.to_json())?,
     }
-}
\ No newline at end of file
+    out.flush()?;
+    Ok(())
+}
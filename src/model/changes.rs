@@ -1,100 +1,88 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
 #![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
 
-use crate::constants::{Val};
-
-#[derive(Debug)]
-pub struct Change {
-    // is add new change before or after a chunk
-    // indicated by add_or_del_idx
-    pub before: Option<bool>,
-    // incase its chunk to be deleted
-    // in original file this will be true
-    pub del_chunk: bool,
-    // this is treated as per del_chunk!
-    pub add_or_del_idx: usize,
-    // when new changes to add
-    // this will be Some
-    pub content: Option<Vec<u8>>,
+/// Single delta instruction, modified file is the output of all
+/// instructions of a delta written one after another.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// copy len bytes of original file starting at byte offset
+    Copy {
+        offset: usize,
+        len: usize,
+    },
+    /// bytes not found in original file
+    Literal(Vec<u8>),
 }
 
-impl PartialEq for Change {
-    fn eq(&self, other: &Self) -> bool {
-        self.before == other.before &&
-        self.content == other.content &&
-        self.del_chunk == other.del_chunk &&
-        self.add_or_del_idx == other.add_or_del_idx 
+impl Change {
+    pub fn copy(offset: usize, len: usize) -> Self {
+        Self::Copy { offset, len }
+    }
+
+    pub fn literal(bytes: Vec<u8>) -> Self {
+        Self::Literal(bytes)
     }
-}
 
-impl Clone for Change {
-    fn clone(&self) -> Self {
-        Self {
-            before: self.before.clone(),
-            content: self.content.clone(),
-            del_chunk: self.del_chunk.clone(),
-            add_or_del_idx: self.add_or_del_idx.clone(),
+    // number of bytes this instruction writes to modified file
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Copy { len, .. } => *len,
+            Self::Literal(bytes) => bytes.len(),
         }
     }
-}
 
-impl Change {
-    pub fn new(
-        before: Option<bool>,
-        del_chunk: bool,
-        // c_size is None if del_chunk is true and 
-        // last_or_cur_match_idx is 0
-        // is Some if del_chunk is false and 
-        // last_or_cur_match_idx is != 0
-        c_size: Option<usize>, 
-        content: Option<Vec<u8>>,
-        // if del_chunk true, del index else last match index
-        // meaning of 0 depends on del_chunk!
-        last_or_cur_match_idx: usize
-    ) -> Self {
-        
-        if del_chunk {
-            // means a chunk is to be flagged removed!
-            Self {
-                before,
-                del_chunk,
-                content: None,
-                add_or_del_idx: last_or_cur_match_idx * c_size.unwrap(),
-            }
-        } else { 
-            // means new changes to add!
-            let mut add_idx = last_or_cur_match_idx;
-            if c_size.is_some() {
-                add_idx = last_or_cur_match_idx * c_size.unwrap();
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // absorbs next instruction when both can be written as one,
+    // i.e. a copy continuing right where this one ends
+    // or a literal following a literal
+    pub fn try_merge(&mut self, next: &Change) -> bool {
+        match (self, next) {
+            (
+                Self::Copy { offset, len },
+                Self::Copy { offset: n_offset, len: n_len }
+            ) if *offset + *len == *n_offset => {
+                *len += n_len;
+                true
             }
-            Self {
-                before,
-                content,
-                del_chunk,
-                add_or_del_idx: add_idx,
+            (Self::Literal(bytes), Self::Literal(n_bytes)) => {
+                bytes.extend_from_slice(n_bytes);
+                true
             }
+            _ => false,
         }
     }
-
 }
 
 
 #[cfg(test)]
 mod change_test {
     use super::*;
+    use crate::constants::Val;
 
     #[test]
     fn ut_createNewChange_works() {
-        let chg = Change::new(
-            Some(true),
-            false, 
-            Some(Val::TEST_C_SIZE), 
-            Some(Vec::new()), 
-            1
-        );
-        assert_eq!(chg.del_chunk, false);
-        assert_eq!(chg.add_or_del_idx, Val::TEST_C_SIZE);
-        assert_eq!(chg.content.unwrap(), []);
+        let chg = Change::copy(Val::TEST_C_SIZE, Val::TEST_C_SIZE);
+        assert_eq!(chg, Change::Copy { offset: Val::TEST_C_SIZE, len: Val::TEST_C_SIZE });
+        assert_eq!(chg.len(), Val::TEST_C_SIZE);
+
+        let chg = Change::literal(Vec::new());
+        assert!(chg.is_empty());
+    }
+
+    #[test]
+    fn ut_tryMerge_works() {
+        let mut chg = Change::copy(0, 4);
+        assert!(chg.try_merge(&Change::copy(4, 4)));
+        assert_eq!(chg, Change::copy(0, 8));
+        // not adjacent in original
+        assert!(!chg.try_merge(&Change::copy(12, 4)));
+        assert!(!chg.try_merge(&Change::literal(vec![1])));
+
+        let mut chg = Change::literal(vec![1]);
+        assert!(chg.try_merge(&Change::literal(vec![2])));
+        assert_eq!(chg, Change::literal(vec![1, 2]));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,351 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Chunkers decide where a file is cut into chunks.
+
+    Fixed:  every chunk is c_size bytes, last one may be shorter.
+    Gear:   content defined chunking (FastCDC), a chunk ends where gear hash
+            of its bytes has enough zero bits. chunks are min..=max bytes,
+            c_size on average. cut points depend on content only,
+            so they survive bytes inserted or removed before them.
+*/
+
+use std::io::Read;
+use crate::fio::FileIO;
+use crate::constants::Val;
+use crate::error::{Error, Result};
+use crate::hashing::gear::Gear;
+use crate::fio::codec::{Encoder, Decoder};
+
+/// How a file is cut into chunks, recorded in signature and delta files.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Chunker {
+    #[default]
+    Fixed,
+    Gear {
+        min: usize,
+        max: usize,
+    },
+}
+
+impl Chunker {
+    // gear chunker with usual bounds around average size
+    pub fn gear(avg: usize) -> Self {
+        Self::Gear {
+            min: (avg / 4).max(1),
+            max: avg.saturating_mul(4),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Fixed => "fixed",
+            Self::Gear { .. } => "gear",
+        }
+    }
+
+    pub fn check(&self, c_size: usize) -> Result<()> {
+        if c_size == 0 {
+            return Err(Error::InvalidChunkSize("must be non zero".to_owned()));
+        }
+        if let Self::Gear { min, max } = *self {
+            if min == 0 || min > c_size || c_size > max {
+                return Err(Error::InvalidChunkSize(format!(
+                    "need 0 < min <= avg <= max, got {}, {}, {}",
+                    min,
+                    c_size,
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // longest chunk this chunker may cut
+    pub fn max_len(&self, c_size: usize) -> usize {
+        match self {
+            Self::Fixed => c_size,
+            Self::Gear { max, .. } => *max,
+        }
+    }
+
+    // length of first chunk of bytes,
+    // bytes must hold max_len bytes unless stream ends in there
+    pub fn cut(&self, bytes: &[u8], c_size: usize) -> usize {
+        match *self {
+            Self::Fixed => bytes.len().min(c_size),
+            Self::Gear { min, max } => Self::gear_cut(bytes, min, c_size, max),
+        }
+    }
+
+    // FastCDC with normalized chunking: a stricter mask before average size
+    // and a looser one after, keeps most chunks close to average
+    fn gear_cut(bytes: &[u8], min: usize, avg: usize, max: usize) -> usize {
+        let len = bytes.len();
+        if len <= min {
+            return len;
+        }
+        let end = len.min(max);
+        let normal = avg.min(end);
+        let bits = usize::BITS - 1 - avg.leading_zeros();
+        let mask_s = Self::top_bits(bits + 2);
+        let mask_l = Self::top_bits(bits.saturating_sub(2));
+
+        let mut gear = Gear::new();
+        for (i, byte) in bytes.iter().enumerate().take(end).skip(min) {
+            gear.roll_in(*byte);
+            let mask = if i < normal { mask_s } else { mask_l };
+            if gear.hash & mask == 0 {
+                return i + 1;
+            }
+        }
+        end
+    }
+
+    // top bits of hash depend on more bytes than low ones
+    fn top_bits(n: u32) -> u64 {
+        match n {
+            0 => 0,
+            n => !0u64 << (64 - n.min(64)),
+        }
+    }
+
+    // chunks of a whole buffer, cut in place
+    pub fn chunks<'a>(&self, mut bytes: &'a [u8], c_size: usize) -> impl Iterator<Item = &'a [u8]> {
+        let chunker = *self;
+        std::iter::from_fn(move || {
+            if bytes.is_empty() {
+                return None;
+            }
+            let (chunk, rest) = bytes.split_at(chunker.cut(bytes, c_size));
+            bytes = rest;
+            Some(chunk)
+        })
+    }
+
+    // next chunks making up at least len bytes, unless chunks run out
+    pub fn take_batch<'a>(chunks: &mut impl Iterator<Item = &'a [u8]>, len: usize) -> Vec<&'a [u8]> {
+        let mut batch = Vec::new();
+        let mut taken = 0;
+        while taken < len {
+            match chunks.next() {
+                Some(chunk) => {
+                    taken += chunk.len();
+                    batch.push(chunk);
+                }
+                None => break,
+            }
+        }
+        batch
+    }
+
+    pub fn encode(&self, enc: &mut Encoder) {
+        match *self {
+            Self::Fixed => {
+                enc.put_u8(Val::CHUNKER_FIXED);
+            }
+            Self::Gear { min, max } => {
+                enc.put_u8(Val::CHUNKER_GEAR)
+                    .put_varint(min as u64)
+                    .put_varint(max as u64);
+            }
+        }
+    }
+
+    // None means unknown chunker or malformed bytes
+    pub fn decode(dec: &mut Decoder) -> Option<Self> {
+        match dec.get_u8()? {
+            Val::CHUNKER_FIXED => Some(Self::Fixed),
+            Val::CHUNKER_GEAR => Some(Self::Gear {
+                min: usize::try_from(dec.get_varint()?).ok()?,
+                max: usize::try_from(dec.get_varint()?).ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Cuts a byte stream into chunks, holding a bounded buffer only.
+pub struct ChunkReader<R> {
+    reader: R,
+    chunker: Chunker,
+    c_size: usize,
+    buf: Vec<u8>,
+    // unread bytes are buf[start..end]
+    start: usize,
+    end: usize,
+    eof: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub fn new(reader: R, chunker: Chunker, c_size: usize) -> Self {
+        Self::with_buf_len(reader, chunker, c_size, Val::READ_BUF_SIZE)
+    }
+
+    // bigger buffer makes bigger batches for next_chunks,
+    // it's at least twice the longest chunk anyway
+    pub fn with_buf_len(reader: R, chunker: Chunker, c_size: usize, len: usize) -> Self {
+        let len = len.max(2 * chunker.max_len(c_size));
+        Self {
+            reader,
+            chunker,
+            c_size,
+            buf: vec![0u8; len],
+            start: 0,
+            end: 0,
+            eof: false,
+        }
+    }
+
+    pub fn next_chunk(&mut self) -> Result<Option<&[u8]>> {
+        if !self.eof && self.end - self.start < self.chunker.max_len(self.c_size) {
+            self.fill()?;
+        }
+        if self.start == self.end {
+            return Ok(None);
+        }
+        let s = self.start;
+        let n = self.chunker.cut(&self.buf[s..self.end], self.c_size);
+        self.start += n;
+        Ok(Some(&self.buf[s..s + n]))
+    }
+
+    // all chunks that can be cut from a full buffer, in order,
+    // empty only once stream is over
+    pub fn next_chunks(&mut self) -> Result<Vec<&[u8]>> {
+        if !self.eof {
+            self.fill()?;
+        }
+        let max_len = self.chunker.max_len(self.c_size);
+        let mut ranges = Vec::new();
+        // a chunk is cut only once its longest possible bytes are in
+        while self.start < self.end && (self.eof || self.end - self.start >= max_len) {
+            let s = self.start;
+            self.start += self.chunker.cut(&self.buf[s..self.end], self.c_size);
+            ranges.push(s..self.start);
+        }
+        Ok(ranges.into_iter().map(|range| &self.buf[range]).collect())
+    }
+
+    // keep unread bytes, fill up the rest
+    fn fill(&mut self) -> Result<()> {
+        self.buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+        let n = FileIO::read_full(&mut self.reader, &mut self.buf[self.end..])?;
+        self.end += n;
+        self.eof = self.end < self.buf.len();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod chunker_test {
+    use super::*;
+    use crate::test_utils::bytes;
+
+    fn chunk_lens(bytes: &[u8], chunker: Chunker, c_size: usize) -> Vec<usize> {
+        let mut reader = ChunkReader::new(bytes, chunker, c_size);
+        let mut lens = Vec::new();
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            lens.push(chunk.len());
+        }
+        lens
+    }
+
+    #[test]
+    fn ut_fixedChunks_works() {
+        let lens = chunk_lens("sample data for rolling hash diff.".as_bytes(), Chunker::Fixed, 4);
+        assert_eq!(lens, [vec![4; 8], vec![2]].concat());
+    }
+
+    #[test]
+    fn ut_gearChunks_works() {
+        let data = bytes(300_000, 7);
+        let chunker = Chunker::gear(1024);
+        let lens = chunk_lens(&data, chunker, 1024);
+
+        assert_eq!(lens.iter().sum::<usize>(), data.len());
+        // last one may be short
+        assert!(lens[..lens.len() - 1].iter().all(|&len| (256..=4096).contains(&len)));
+        let avg = data.len() / lens.len();
+        assert!((512..2048).contains(&avg), "average {}", avg);
+    }
+
+    #[test]
+    fn ut_gearChunksSurviveInsert_works() {
+        let data = bytes(100_000, 11);
+        let mut modified = bytes(100, 3);
+        modified.extend_from_slice(&data);
+        let chunker = Chunker::gear(512);
+
+        let ends = |bytes: &[u8], skip: usize| {
+            let mut end = 0;
+            chunk_lens(bytes, chunker, 512).iter().map(|len| {
+                end += len;
+                end - skip
+            }).collect::<Vec<usize>>()
+        };
+        let original = ends(&data, 0);
+        let shifted = ends(&modified, 100);
+        // after first few chunks, cut points are the same
+        let common = shifted.iter().filter(|end| original.contains(end)).count();
+        assert!(common + 3 >= original.len(), "{} of {}", common, original.len());
+    }
+
+    #[test]
+    fn ut_nextChunks_works() {
+        let data = bytes(100_000, 5);
+        for chunker in [Chunker::Fixed, Chunker::gear(256)] {
+            let mut reader = ChunkReader::with_buf_len(&data[..], chunker, 256, 4096);
+            let mut lens = Vec::new();
+            loop {
+                let chunks = reader.next_chunks().unwrap();
+                if chunks.is_empty() {
+                    break;
+                }
+                lens.extend(chunks.iter().map(|chunk| chunk.len()));
+            }
+            // batches cut same chunks as one by one
+            assert_eq!(lens, chunk_lens(&data, chunker, 256));
+
+            // and so does cutting a buffer in place
+            let mut chunks = chunker.chunks(&data, 256);
+            let batch = Chunker::take_batch(&mut chunks, 50_000);
+            assert!(batch.iter().map(|chunk| chunk.len()).sum::<usize>() >= 50_000);
+            let rest: Vec<&[u8]> = chunks.collect();
+            let lens_in_place: Vec<usize> = batch.iter().chain(&rest).map(|chunk| chunk.len()).collect();
+            assert_eq!(lens_in_place, lens);
+        }
+    }
+
+    #[test]
+    fn ut_check_fails() {
+        assert!(Chunker::Fixed.check(4).is_ok());
+        assert!(Chunker::gear(4).check(4).is_ok());
+        assert!(matches!(Chunker::Fixed.check(0), Err(Error::InvalidChunkSize(_))));
+        assert!(matches!(
+            Chunker::Gear { min: 8, max: 16 }.check(4),
+            Err(Error::InvalidChunkSize(_))
+        ));
+        assert!(matches!(
+            Chunker::Gear { min: 0, max: 16 }.check(4),
+            Err(Error::InvalidChunkSize(_))
+        ));
+    }
+
+    #[test]
+    fn ut_encodeDecode_works() {
+        // sizes past u32 too
+        let big = Chunker::gear(u32::MAX as usize + 1);
+        for chunker in [Chunker::Fixed, Chunker::Gear { min: 2, max: 300 }, big] {
+            let mut enc = Encoder::new();
+            chunker.encode(&mut enc);
+            let mut dec = Decoder::new(&enc.buf);
+            assert_eq!(Chunker::decode(&mut dec), Some(chunker));
+            assert!(dec.is_empty());
+        }
+        assert_eq!(Chunker::decode(&mut Decoder::new(&[9])), None);
+    }
+}
@@ -1,43 +1,162 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
 #![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
 
 /*
-    During patching, this program's output works the following way:
-    
-    1. first all the Changes (Change object) with del_chunk true, will be applied where 
-        add_or_del_idx indicates index of the chunk in original file.
-    2. After 1st step, apply Changes with del_chunk = false before/after the chunk whose index 
-        is indicated by add_or_del_idx . before or after is indicated by before flag
-
-    Note:
-        for before true apply those changes first, then apply those for which before is false  
-*/
+    Delta is a list of instructions (Change) in order of modified file:
+
+        Copy { offset, len }   len bytes of original file from byte offset
+        Literal(bytes)         bytes not found in original file
 
-use super::hash;
+    Patching writes output of every instruction one after another.
+    Adjacent copies and adjacent literals are merged while building the list.
+    With an output (with_output), changes are written to a delta file as soon
+    as they can't merge anymore, or are literals of Val::LITERAL_FLUSH_SIZE bytes,
+    and list only holds the last one.
+
+    Threaded search (fixed chunks) reads modified file in blocks and splits
+    window starts of a block into one segment per thread, windows of a segment
+    may run c_size - 1 bytes into next one. each thread searches its segment
+    greedily as if it started there. stitching walks segments in order: where
+    serial search would stand inside a match a thread made, it searches serially
+    up to where that thread was free again, from there both agree.
+    so delta is the same as the serial one, whatever the number of threads.
+    false positives are counted after stitching, only at window starts serial
+    search looks up, so their count doesn't depend on number of threads either.
+*/
+use std::thread;
+use std::io::{Read, BufRead, Write, ErrorKind};
 use crate::fio::FileIO;
+use crate::constants::Val;
+use crate::error::Result;
 use super::changes::Change;
-use super::signature::Signature;
+use super::chunker::{Chunker, ChunkReader};
+use super::signature::{Signature, Probe};
+use super::delta_file::{DeltaFile, DeltaWriter};
+use crate::hashing::blake::Blake3;
+use crate::hashing::WeakHash;
+use crate::traits::RollingHash;
 use crate::hashing::adler::Adler32;
+use crate::hashing::buzhash::Buzhash;
+use crate::hashing::rabin_karp::RabinKarp;
+
+
+// where search of modified file stands between blocks
+struct SearchState {
+    // chunk following last match, preferred for next match
+    next_idx: usize,
+    // search stands right after a match (or at file start),
+    // only there a short last chunk may match
+    fresh: bool,
+}
 
+impl SearchState {
+    fn new() -> Self {
+        Self {
+            next_idx: 0,
+            fresh: true,
+        }
+    }
+}
+
+// what a search of some window starts found
+struct Found {
+    // (window start, chunk index) of each match
+    matches: Vec<(usize, usize)>,
+    // window starts L1 hash hit but L2 hash didn't confirm
+    false_positives: Vec<usize>,
+    // first position not searched
+    next: usize,
+}
 
+/// Builds list of changes turning original file (known only by its signature)
+/// into a modified file.
 #[derive(Debug, PartialEq)]
 pub struct DiffingDelta<'local> {
-    pub sign: &'local mut Signature,
+    pub sign: &'local Signature,
     pub list: Vec<Change>,
-
+    /// times each signature chunk got matched,
+    /// 0 means chunk is deleted, more than 1 means it is reused
+    pub uses: Vec<usize>,
+    /// size and strong checksum of whole modified file
+    pub f_size: usize,
+    pub f_sum: [u8; 32],
+    /// bytes of modified file sent as they are, written out or not
+    pub literal_bytes: usize,
+    /// L1 hits L2 didn't confirm, at window starts serial search looks up
+    pub false_positives: usize,
+    // delta file changes are written to instead of kept in list
+    out: Option<DeltaWriter<Box<dyn Write + 'local>>>,
 }
 
 impl<'local> DiffingDelta<'local> {
-    pub fn new(sign: &'local mut Signature) -> Self {
+    pub fn new(sign: &'local Signature) -> Self {
         Self {
             sign,
             list: Vec::new(),
+            uses: vec![0; sign.len()],
+            f_size: 0,
+            f_sum: [0u8; 32],
+            literal_bytes: 0,
+            false_positives: 0,
+            out: None,
         }
     }
 
+    // changes go to a delta file written into out, header is written right away,
+    // finish() ends it
+    pub fn with_output(sign: &'local Signature, out: impl Write + 'local) -> Self {
+        let mut delta = Self::new(sign);
+        let out: Box<dyn Write + 'local> = Box::new(out);
+        delta.out = Some(DeltaWriter::new(out, &DeltaFile::for_sign(sign)));
+        delta
+    }
+
+    // merges change into last one when possible
     pub fn add(&mut self, change: Change) {
-        self.list.push(change);
+        if change.is_empty() {
+            return;
+        }
+        if let Change::Literal(bytes) = &change {
+            self.literal_bytes += bytes.len();
+        }
+        let merged = self.list.last_mut().is_some_and(|last| last.try_merge(&change));
+        if !merged {
+            self.list.push(change);
+        }
+        self.write_out();
+    }
+
+    // writes changes which won't grow anymore, and a big enough last literal
+    fn write_out(&mut self) {
+        let Some(out) = &mut self.out else {
+            return;
+        };
+        let keep = match self.list.last() {
+            Some(Change::Literal(bytes)) if bytes.len() >= Val::LITERAL_FLUSH_SIZE => 0,
+            Some(_) => 1,
+            None => 0,
+        };
+        let done = self.list.len() - keep;
+        self.list.drain(..done).for_each(|change| out.put(&change));
+    }
+
+    /// Writes changes left and end of delta file to output,
+    /// returns size of whole delta file.
+    ///
+    /// Without output, size delta file of list would have.
+    pub fn finish(&mut self) -> Result<usize> {
+        let Some(mut out) = self.out.take() else {
+            return Ok(self.encoded_len());
+        };
+        self.list.drain(..).for_each(|change| out.put(&change));
+        out.finish(self.f_size, &self.f_sum)
+    }
+
+    // size of delta file of list, nothing is encoded into memory
+    pub fn encoded_len(&self) -> usize {
+        let mut writer = DeltaWriter::new(std::io::sink(), &DeltaFile::for_sign(self.sign));
+        self.list.iter().for_each(|change| writer.put(change));
+        writer.finish(self.f_size, &self.f_sum).unwrap_or_default()
     }
 
     pub fn get(&self, index: usize) -> Option<&Change> {
@@ -48,149 +167,504 @@ impl<'local> DiffingDelta<'local> {
         self.list.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // indexes of original chunks not in modified file
+    pub fn deleted_chunks(&self) -> Vec<usize> {
+        self.chunks_where(|uses| uses == 0)
+    }
+
+    // indexes of original chunks found more than once in modified file
+    pub fn reused_chunks(&self) -> Vec<usize> {
+        self.chunks_where(|uses| uses > 1)
+    }
+
+    fn chunks_where(&self, pred: impl Fn(usize) -> bool) -> Vec<usize> {
+        self.uses
+            .iter()
+            .enumerate()
+            .filter(|(_, &uses)| pred(uses))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
     pub fn file_to_delta_list(
         &mut self,
         path: &str,
         c_size: usize
-    ) -> Option<()> {
-        // get all bytes from file
-        let bytes = FileIO::read_file_to_bytes(path);
-        if bytes.is_none() { return None; }
-
-        let bytes = bytes.unwrap();
-        let len = bytes.len();
-        let mut adler = Adler32::new();
-        let mut last_match_idx = 0usize;
+    ) -> Result<()> {
+        // file is streamed, never loaded whole
+        let reader = FileIO::open_file_reader(path)?;
+        self.reader_to_delta_list(reader, c_size)
+            .map_err(|e| e.with_path(path))
+    }
+
+    // same as above but for any byte stream, read a buffer at a time,
+    // memory used is delta list (unless written out), one window
+    // and up to Val::LITERAL_FLUSH_SIZE unmatched bytes
+    pub fn reader_to_delta_list<R: BufRead>(
+        &mut self,
+        reader: R,
+        c_size: usize
+    ) -> Result<()> {
+        self.sign.chunker.check(c_size)?;
+        if self.sign.chunker != Chunker::Fixed {
+            return self.chunks_to_delta_list(reader, c_size);
+        }
+        // weak hash is picked once here, not per byte
+        match self.sign.weak {
+            WeakHash::Adler32 => self.rolling_to_delta_list(reader, c_size, Adler32::new()),
+            WeakHash::RabinKarp => self.rolling_to_delta_list(reader, c_size, RabinKarp::new()),
+            WeakHash::Buzhash => self.rolling_to_delta_list(reader, c_size, Buzhash::new()),
+        }
+    }
+
+    // same as above with search spread over that many threads,
+    // delta is the same whatever the number of threads
+    pub fn reader_to_delta_list_threaded<R: BufRead>(
+        &mut self,
+        reader: R,
+        c_size: usize,
+        threads: usize
+    ) -> Result<()> {
+        if threads <= 1 {
+            return self.reader_to_delta_list(reader, c_size);
+        }
+        self.sign.chunker.check(c_size)?;
+        if self.sign.chunker != Chunker::Fixed {
+            return self.chunks_to_delta_list_threaded(reader, c_size, threads);
+        }
+        let seg_len = Val::THREAD_BATCH_SIZE;
+        match self.sign.weak {
+            WeakHash::Adler32 => self.segments_to_delta_list::<R, Adler32>(reader, c_size, threads, seg_len),
+            WeakHash::RabinKarp => self.segments_to_delta_list::<R, RabinKarp>(reader, c_size, threads, seg_len),
+            WeakHash::Buzhash => self.segments_to_delta_list::<R, Buzhash>(reader, c_size, threads, seg_len),
+        }
+    }
+
+    // same as above for a whole file in memory (e.g. memory mapped),
+    // it is searched in place, nothing is copied but literals
+    pub fn bytes_to_delta_list(
+        &mut self,
+        bytes: &[u8],
+        c_size: usize,
+        threads: usize
+    ) -> Result<()> {
+        self.sign.chunker.check(c_size)?;
+        let threads = threads.max(1);
+        if self.sign.chunker != Chunker::Fixed {
+            return self.slice_chunks_to_delta_list(bytes, c_size, threads);
+        }
+        let seg_len = Val::THREAD_BATCH_SIZE;
+        match self.sign.weak {
+            WeakHash::Adler32 => self.slice_to_delta_list::<Adler32>(bytes, c_size, threads, seg_len),
+            WeakHash::RabinKarp => self.slice_to_delta_list::<RabinKarp>(bytes, c_size, threads, seg_len),
+            WeakHash::Buzhash => self.slice_to_delta_list::<Buzhash>(bytes, c_size, threads, seg_len),
+        }
+    }
+
+    fn rolling_to_delta_list<R: BufRead, H: RollingHash>(
+        &mut self,
+        mut reader: R,
+        c_size: usize,
+        mut roller: H
+    ) -> Result<()> {
+        let mut blake = Blake3::new();
+        self.f_size = 0;
+        self.uses = vec![0; self.sign.len()];
+        // chunk following last match, preferred for next match
+        let mut next_idx = 0usize;
         let mut literals = Vec::<u8>::new();
-        let mut matched_chunks = Vec::<usize>::new();
-        for (i, byte) in bytes.iter().enumerate() {
-            adler.roll_in(*byte);
-            
-            if adler.window.len() < c_size && i < len-1 {
-                continue;
-            }
-            // means no match on prev iteration
-            if adler.window.len() > c_size {
-                // this makes window sliding/rolling
-                adler.roll_out();
-                // save rolled out bytes into literals
-                // so it could be added to change object later-on
-                literals.push(adler.rolled_out_byte);
-            }
-
-            // try getting a match
-            let idx = self.sign.try_get_position_of(&adler);
-            // if we have match
-            if idx.is_some() {
-                last_match_idx = idx.unwrap();
-                // save cur match idx for later use
-                matched_chunks.push(idx.unwrap());
-                // if we have some unsaved changes
-                if literals.len() > 0 {
-                    let mut temp_cz = Some(c_size);
-                    if last_match_idx == 0 {
-                        temp_cz = None;
+        loop {
+            let buf = match reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if buf.is_empty() {
+                break;
+            }
+            for &byte in buf {
+                roller.roll_in(byte);
+                if roller.window_len() < c_size {
+                    continue;
+                }
+                // means no match on prev iteration
+                if roller.window_len() > c_size {
+                    // this makes window sliding/rolling,
+                    // rolled out byte is not in original
+                    literals.extend(roller.roll_out());
+                    if literals.len() >= Val::LITERAL_FLUSH_SIZE {
+                        self.add(Change::literal(std::mem::take(&mut literals)));
                     }
-                    // save change into delta list
-                    self.handle_new_change(
-                        Some(true),
-                        last_match_idx,
-                        temp_cz,
-                        literals.clone()
-                    );
-                    // reset literals
-                    literals.drain(..);
                 }
-                // reset adler for finding next chunk
-                adler.reset();
+                self.match_window(&mut roller, &mut literals, &mut next_idx);
             }
+            blake.write_bytes(buf);
+            self.f_size += buf.len();
+            let len = buf.len();
+            reader.consume(len);
         }
-        // fill chunks which are in signature list
-        // but not in delta list
-        // indicate same by del_chunk flag
-        self.fill_missing_chunks_if_any(&matched_chunks, c_size);
-        
-        // corner-case: (where above loop fails)
-        // we have missed some new changes in main loop
-        // because it ended or last chunk didn't match
-        // and remained in adler.window
-        // check if there are chars in window which didn't got matched
-        // and loop ended
-        // if nothing in there then check whats in literals
-        if adler.window.len() > 0 || literals.len() > 0 {
-            if adler.window.len() > 0 {
-                // put window chars into literals
-                literals.append(&mut adler.window)
-            }
-            // just handling indexing
-            if matched_chunks.len() > 0 && last_match_idx == 0 {
-                last_match_idx += c_size;
-            } else {
-                last_match_idx = (last_match_idx * c_size) + c_size;
-            }
-            // finally add the literals as new change
-            // into delta list but with 'before' flag set false
-            // means add these changes 'after' last chunk matched!
-            self.handle_new_change(
-                Some(false),
-                last_match_idx,
-                None,
-                literals
-            )
+
+        // a short window still gets a chance at the end,
+        // as last chunk of original may be shorter than c_size
+        if (1..c_size).contains(&roller.window_len()) {
+            self.match_window(&mut roller, &mut literals, &mut next_idx);
         }
+        // whatever did not match till the end
+        literals.extend_from_slice(roller.window());
+        self.add(Change::literal(literals));
 
-        return Some(());
+        self.f_sum = blake.digest();
+        Ok(())
     }
 
-    fn handle_new_change(
+    // adds a copy if window is a chunk of original, unmatched bytes before it first
+    fn match_window<H: RollingHash>(
         &mut self,
-        before: Option<bool>,
-        last_match_idx: usize, 
-        c_size: Option<usize>,
-        literals: Vec<u8> 
+        roller: &mut H,
+        literals: &mut Vec<u8>,
+        next_idx: &mut usize
     ) {
-        // create a Change object
-        let change = Change::new(
-            before,
-            false,
-            c_size,
-            Some(literals.clone()),
-            last_match_idx
-        );
-        // finally save into the delta list!!
-        self.add(change);
-    }
-
-    fn fill_missing_chunks_if_any(
+        let probe = self.sign.probe_position_of(roller, *next_idx);
+        if let Some(idx) = self.counted(probe) {
+            self.uses[idx] += 1;
+            *next_idx = idx + 1;
+            if !literals.is_empty() {
+                self.add(Change::literal(std::mem::take(literals)));
+            }
+            self.add(Change::copy(self.sign.offset_of(idx), roller.window_len()));
+            // reset roller for finding next chunk
+            roller.reset();
+        }
+    }
+
+    // index of chunk probe matched, an L1 hit L2 didn't confirm is counted
+    fn counted(&mut self, probe: Probe) -> Option<usize> {
+        if probe == Probe::FalsePositive {
+            self.false_positives += 1;
+        }
+        probe.index()
+    }
+
+    // threaded counterpart of rolling_to_delta_list,
+    // seg_len is number of window starts each thread searches per block
+    fn segments_to_delta_list<R: Read, H: RollingHash + Default>(
         &mut self,
-        matched_chunks: &Vec<usize>,
-        cz: usize
-    ) {
-        let mut i = 0;
-        let mut j = 0;
-        let len1 = matched_chunks.len();
-        let len2 = self.sign.list.len();
+        mut reader: R,
+        c_size: usize,
+        threads: usize,
+        seg_len: usize
+    ) -> Result<()> {
+        let mut blake = Blake3::new();
+        self.f_size = 0;
+        self.uses = vec![0; self.sign.len()];
+        let mut state = SearchState::new();
+        // unsearched bytes, first one is where serial search stands
+        let mut data = Vec::<u8>::new();
+
         loop {
-            // j depends on sign list
-            // we will terminate loop on that only
-            // as matched chunks may be less than that
-            if j == len2 {
+            let want = threads * seg_len + c_size - 1;
+            let old = data.len();
+            data.resize(want, 0);
+            let n = FileIO::read_full(&mut reader, &mut data[old..])?;
+            data.truncate(old + n);
+            blake.write_bytes(&data[old..]);
+            self.f_size += n;
+            let eof = data.len() < want;
+
+            let cursor = self.search_block::<H>(&data, eof, c_size, threads, &mut state);
+            if eof {
                 break;
             }
-            
-            if  i < len1 && matched_chunks[i] == j{
-                // we hit a matched block in signatures
-                i += 1;
+            data.drain(..cursor);
+        }
+        self.f_sum = blake.digest();
+        Ok(())
+    }
+
+    // same as above for a whole file in memory, blocks are searched in place
+    fn slice_to_delta_list<H: RollingHash + Default>(
+        &mut self,
+        bytes: &[u8],
+        c_size: usize,
+        threads: usize,
+        seg_len: usize
+    ) -> Result<()> {
+        self.f_size = bytes.len();
+        self.f_sum = Blake3::sum256(bytes);
+        self.uses = vec![0; self.sign.len()];
+        let mut state = SearchState::new();
+        let mut base = 0;
+
+        loop {
+            let end = bytes.len().min(base + threads * seg_len + c_size - 1);
+            let eof = end == bytes.len();
+            base += self.search_block::<H>(&bytes[base..end], eof, c_size, threads, &mut state);
+            if eof {
+                return Ok(());
+            }
+        }
+    }
+
+    // adds changes for a block of modified file, data[0] being where search stands,
+    // returns how many bytes of block are done with
+    fn search_block<H: RollingHash + Default>(
+        &mut self,
+        data: &[u8],
+        eof: bool,
+        c_size: usize,
+        threads: usize,
+        state: &mut SearchState
+    ) -> usize {
+        // windows starting from here on need bytes of next block
+        let until = match eof {
+            true => data.len(),
+            false => data.len() + 1 - c_size,
+        };
+        let (found, false_positives) = self.search_segments::<H>(data, c_size, until, threads, state.next_idx);
+        self.false_positives += false_positives;
+
+        let mut pos = 0;
+        for (start, idx) in found {
+            // thread picked idx among equal chunks, serial search may prefer another one
+            let idx = self.sign.find(&self.sign.list[idx], state.next_idx).unwrap_or(idx);
+            self.uses[idx] += 1;
+            state.next_idx = idx + 1;
+            self.add(Change::literal(data[pos..start].to_owned()));
+            self.add(Change::copy(self.sign.offset_of(idx), c_size));
+            pos = start + c_size;
+            state.fresh = true;
+        }
+
+        if eof {
+            let tail = &data[pos..];
+            let idx = match state.fresh && !tail.is_empty() && tail.len() < c_size {
+                true => self.counted(self.sign.probe(&self.sign.hash_of(tail), state.next_idx)),
+                false => None,
+            };
+            match idx {
+                Some(idx) if self.sign.len_of(idx) == tail.len() => {
+                    self.uses[idx] += 1;
+                    self.add(Change::copy(self.sign.offset_of(idx), tail.len()));
+                }
+                _ => self.add(Change::literal(tail.to_owned())),
+            }
+            return data.len();
+        }
+        let cursor = pos.max(until);
+        state.fresh &= cursor == pos;
+        self.add(Change::literal(data[pos..cursor].to_owned()));
+        cursor
+    }
+
+    // matches (window start, chunk index) of window starts below until,
+    // as serial greedy search would find them, and its false positives
+    fn search_segments<H: RollingHash + Default>(
+        &self,
+        data: &[u8],
+        c_size: usize,
+        until: usize,
+        threads: usize,
+        preferred: usize
+    ) -> (Vec<(usize, usize)>, usize) {
+        if until == 0 {
+            return (Vec::new(), 0);
+        }
+        // segments much shorter than a chunk would be searched serially anyway
+        let count = threads.min(until.div_ceil(c_size)).max(1);
+        if count == 1 {
+            let found = Self::search::<H>(self.sign, data, c_size, 0, until, preferred);
+            return (found.matches, found.false_positives.len());
+        }
+        let seg_len = until.div_ceil(count);
+        let segments: Vec<(usize, usize)> = (0..until)
+            .step_by(seg_len)
+            .map(|from| (from, (from + seg_len).min(until)))
+            .collect();
+        let sign = self.sign;
+        let results: Vec<Found> = thread::scope(|scope| {
+            let workers: Vec<_> = segments.iter().map(|&(from, to)| {
+                scope.spawn(move || Self::search::<H>(sign, data, c_size, from, to, preferred))
+            }).collect();
+            workers.into_iter()
+                .map(|worker| worker.join().expect("search thread panicked"))
+                .collect()
+        });
+
+        let mut stitched = Vec::new();
+        let mut false_positives = 0;
+        // where serial search stands
+        let mut cursor = 0;
+        for (&(_, to), found) in segments.iter().zip(results) {
+            let matches = &found.matches;
+            while cursor < to {
+                // thread walked over cursor unless it was inside one of its matches
+                let i = matches.partition_point(|&(start, _)| start < cursor);
+                match i.checked_sub(1).map(|j| matches[j]).filter(|&(start, _)| start + c_size > cursor) {
+                    None => {
+                        // from cursor on, thread looked up what serial search would
+                        let seen = found.false_positives.partition_point(|&p| p < cursor);
+                        false_positives += found.false_positives.len() - seen;
+                        stitched.extend_from_slice(&matches[i..]);
+                        let end = matches[i..].last().map(|&(start, _)| start + c_size);
+                        cursor = end.unwrap_or_default().max(to);
+                    }
+                    Some((start, _)) => {
+                        let free = (start + c_size).min(to);
+                        let more = Self::search::<H>(self.sign, data, c_size, cursor, free, preferred);
+                        false_positives += more.false_positives.len();
+                        stitched.extend(more.matches);
+                        cursor = more.next;
+                    }
+                }
+            }
+        }
+        (stitched, false_positives)
+    }
+
+    // greedy search for windows starting in from..until, a match skips
+    // its whole window. false positives are left to caller to count
+    fn search<H: RollingHash + Default>(
+        sign: &Signature,
+        data: &[u8],
+        c_size: usize,
+        from: usize,
+        until: usize,
+        mut preferred: usize
+    ) -> Found {
+        let mut roller = H::default();
+        let mut matches = Vec::new();
+        let mut false_positives = Vec::new();
+        let mut p = from;
+        while p < until && p + c_size <= data.len() {
+            if roller.window_len() == 0 {
+                data[p..p + c_size].iter().for_each(|byte| roller.roll_in(*byte));
             } else {
-                self.add(Change::new(
-                    None,
-                    true, // this flag make chunk removed
-                    Some(cz),
-                    None,
-                    j,
-                ))
+                roller.roll_out();
+                roller.roll_in(data[p + c_size - 1]);
+            }
+            match sign.probe_position_of(&mut roller, preferred) {
+                Probe::Match(idx) => {
+                    matches.push((p, idx));
+                    preferred = idx + 1;
+                    p += c_size;
+                    roller.reset();
+                }
+                Probe::FalsePositive => {
+                    false_positives.push(p);
+                    p += 1;
+                }
+                Probe::Miss => p += 1,
+            }
+        }
+        Found {
+            matches,
+            false_positives,
+            next: p.max(until),
+        }
+    }
+
+    // content defined chunks don't need a rolling window:
+    // modified stream is cut same way as original, and chunks are looked up whole
+    fn chunks_to_delta_list<R: Read>(
+        &mut self,
+        reader: R,
+        c_size: usize
+    ) -> Result<()> {
+        let mut blake = Blake3::new();
+        let mut chunks = ChunkReader::new(reader, self.sign.chunker, c_size);
+        self.f_size = 0;
+        self.uses = vec![0; self.sign.len()];
+        let mut next_idx = 0usize;
+
+        while let Some(chunk) = chunks.next_chunk()? {
+            blake.write_bytes(chunk);
+            self.f_size += chunk.len();
+            match self.counted(self.sign.probe(&self.sign.hash_of(chunk), next_idx)) {
+                Some(idx) if self.sign.len_of(idx) == chunk.len() => {
+                    self.uses[idx] += 1;
+                    next_idx = idx + 1;
+                    self.add(Change::copy(self.sign.offset_of(idx), chunk.len()));
+                }
+                _ => self.add(Change::literal(chunk.to_owned())),
+            }
+        }
+        self.f_sum = blake.digest();
+        Ok(())
+    }
+
+    // cut points depend on bytes before them, so chunks are still cut in order
+    // and only hashed in parallel
+    fn chunks_to_delta_list_threaded<R: Read>(
+        &mut self,
+        reader: R,
+        c_size: usize,
+        threads: usize
+    ) -> Result<()> {
+        let mut blake = Blake3::new();
+        let buf_len = threads * Val::THREAD_BATCH_SIZE;
+        let mut chunks = ChunkReader::with_buf_len(reader, self.sign.chunker, c_size, buf_len);
+        self.f_size = 0;
+        self.uses = vec![0; self.sign.len()];
+        let mut next_idx = 0usize;
+
+        loop {
+            let batch = chunks.next_chunks()?;
+            if batch.is_empty() {
+                break;
+            }
+            self.add_chunks(&batch, threads, &mut blake, &mut next_idx);
+        }
+        self.f_sum = blake.digest();
+        Ok(())
+    }
+
+    // same as above for a whole file in memory, chunks are cut in place
+    fn slice_chunks_to_delta_list(
+        &mut self,
+        bytes: &[u8],
+        c_size: usize,
+        threads: usize
+    ) -> Result<()> {
+        let mut blake = Blake3::new();
+        self.f_size = 0;
+        self.uses = vec![0; self.sign.len()];
+        let mut next_idx = 0usize;
+        let mut chunks = self.sign.chunker.chunks(bytes, c_size);
+
+        loop {
+            let batch = Chunker::take_batch(&mut chunks, threads * Val::THREAD_BATCH_SIZE);
+            if batch.is_empty() {
+                break;
+            }
+            self.add_chunks(&batch, threads, &mut blake, &mut next_idx);
+        }
+        self.f_sum = blake.digest();
+        Ok(())
+    }
+
+    // looks chunks of modified file up whole
+    fn add_chunks(
+        &mut self,
+        batch: &[&[u8]],
+        threads: usize,
+        blake: &mut Blake3,
+        next_idx: &mut usize
+    ) {
+        let hashes = self.sign.hash_batch(batch, threads, blake);
+        for (chunk, hash) in batch.iter().zip(hashes) {
+            self.f_size += chunk.len();
+            match self.counted(self.sign.probe(&hash, *next_idx)) {
+                Some(idx) if self.sign.len_of(idx) == chunk.len() => {
+                    self.uses[idx] += 1;
+                    *next_idx = idx + 1;
+                    self.add(Change::copy(self.sign.offset_of(idx), chunk.len()));
+                }
+                _ => self.add(Change::literal(chunk.to_vec())),
             }
-            j += 1;
         }
     }
 }
@@ -199,6 +673,7 @@ impl<'local> DiffingDelta<'local> {
 #[cfg(test)]
 mod delta_test {
     use super::*;
+    use crate::test_utils::bytes;
 
     const chunk: &[u8] = &"chunk".as_bytes();
 
@@ -207,7 +682,7 @@ mod delta_test {
         let mut sign = Signature::new();
         sign.add(chunk);
         
-        let delta = DiffingDelta::new(&mut sign);
+        let delta = DiffingDelta::new(&sign);
         
         assert_eq!(delta.list.len(), 0);
         assert_eq!(delta.list.get(0), None);
@@ -216,12 +691,231 @@ mod delta_test {
         assert_ne!(delta.sign.get(0), None);
     }
 
+    #[test]
+    fn ut_readerToDeltaList_works() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let modified = "sample data for rolling hash diff!!".as_bytes();
+
+        let mut sign = Signature::new();
+        sign.reader_to_sign_list(original, 4).unwrap();
+
+        let mut delta = DiffingDelta::new(&sign);
+        delta.reader_to_delta_list(modified, 4).unwrap();
+
+        assert_eq!(delta.list, vec![
+            // all but "f." is still there
+            Change::copy(0, 32),
+            Change::literal("f!!".as_bytes().to_owned()),
+        ]);
+        assert_eq!(delta.f_size, modified.len());
+        assert_eq!(delta.f_sum, Blake3::sum256(modified));
+    }
+
+    #[test]
+    fn ut_readerToDeltaListWithEachWeakHash_works() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let modified = "sample data foradded rolling hash diff.at the end as well!!".as_bytes();
+
+        for weak in [WeakHash::Adler32, WeakHash::RabinKarp, WeakHash::Buzhash] {
+            let mut sign = Signature::new();
+            sign.weak = weak;
+            sign.reader_to_sign_list(original, 4).unwrap();
+
+            let mut delta = DiffingDelta::new(&sign);
+            delta.reader_to_delta_list(modified, 4).unwrap();
+            assert_eq!(delta.list, vec![
+                Change::copy(0, 12),
+                Change::literal("foradded ".as_bytes().to_owned()),
+                Change::copy(16, 16),
+                Change::literal("f.at the end as well!!".as_bytes().to_owned()),
+            ], "{:?}", weak);
+        }
+    }
+
+    #[test]
+    fn ut_readerToDeltaListReusingChunks_works() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        // appended to itself, short last chunk matches only at the end
+        let modified = "sample data for rolling hash difsample data for rolling hash diff.".as_bytes();
+
+        let mut sign = Signature::new();
+        sign.reader_to_sign_list(original, 4).unwrap();
+
+        let mut delta = DiffingDelta::new(&sign);
+        delta.reader_to_delta_list(modified, 4).unwrap();
+
+        // second copy is not written as literals
+        assert_eq!(delta.list, vec![
+            Change::copy(0, 32),
+            Change::copy(0, 34),
+        ]);
+        assert_eq!(delta.reused_chunks(), (0..8).collect::<Vec<usize>>());
+        assert_eq!(delta.deleted_chunks(), Vec::<usize>::new());
+
+        let mut delta = DiffingDelta::new(&sign);
+        delta.reader_to_delta_list("le data for rolling hash diff.".as_bytes(), 4).unwrap();
+        assert_eq!(delta.reused_chunks(), Vec::<usize>::new());
+        assert_eq!(delta.deleted_chunks(), vec![0]);
+    }
+
+    // original cut, repeated, shuffled and sprinkled with new bytes
+    fn modified_of(original: &[u8], seed: u64) -> Vec<u8> {
+        let noise = bytes(original.len(), seed);
+        let mut modified = Vec::new();
+        let mut i = 0;
+        while i < original.len() {
+            let n = 1 + noise[i] as usize * 3;
+            let start = (noise[(i + 1) % noise.len()] as usize * 97) % original.len();
+            match noise[i] % 4 {
+                0 => modified.extend_from_slice(&noise[i..(i + n % 13).min(noise.len())]),
+                1 => modified.extend_from_slice(&original[start..(start + n).min(original.len())]),
+                _ => modified.extend_from_slice(&original[i..(i + n).min(original.len())]),
+            }
+            i += n;
+        }
+        modified
+    }
+
+    #[test]
+    fn ut_segmentsToDeltaList_works() {
+        let original = bytes(20_000, 9);
+        // a few chunks equal to each other, to see same ones picked
+        let original = [&original[..], &original[..3000], &original[2000..]].concat();
+        for weak in [WeakHash::Adler32, WeakHash::Buzhash] {
+            let mut sign = Signature::new();
+            sign.weak = weak;
+            sign.reader_to_sign_list(&original[..], 16).unwrap();
+
+            for seed in 1..4 {
+                let modified = modified_of(&original, seed);
+                let mut serial = DiffingDelta::new(&sign);
+                serial.reader_to_delta_list(&modified[..], 16).unwrap();
+                assert!(serial.list.len() > 10);
+
+                // segments of all kinds of lengths against chunk size
+                for (threads, seg_len) in [(2, 5), (3, 16), (4, 17), (8, 100), (3, 4096)] {
+                    let mut threaded = DiffingDelta::new(&sign);
+                    let res = match weak {
+                        WeakHash::Adler32 => threaded.segments_to_delta_list::<_, Adler32>(
+                            &modified[..], 16, threads, seg_len
+                        ),
+                        _ => threaded.segments_to_delta_list::<_, Buzhash>(
+                            &modified[..], 16, threads, seg_len
+                        ),
+                    };
+                    res.unwrap();
+                    assert_eq!(threaded, serial, "{:?} {} {} {}", weak, seed, threads, seg_len);
+
+                    let mut in_place = DiffingDelta::new(&sign);
+                    let res = match weak {
+                        WeakHash::Adler32 => in_place.slice_to_delta_list::<Adler32>(
+                            &modified, 16, threads, seg_len
+                        ),
+                        _ => in_place.slice_to_delta_list::<Buzhash>(
+                            &modified, 16, threads, seg_len
+                        ),
+                    };
+                    res.unwrap();
+                    assert_eq!(in_place, serial, "{:?} {} {} {}", weak, seed, threads, seg_len);
+                }
+            }
+        }
+    }
+
+    // false positives one search of sign counts
+    fn false_positives_of(sign: &Signature, search: impl FnOnce(&mut DiffingDelta)) -> usize {
+        let mut delta = DiffingDelta::new(sign);
+        search(&mut delta);
+        delta.false_positives
+    }
+
+    #[test]
+    fn ut_falsePositivesThreaded_works() {
+        // few distinct bytes, many windows share L1 hash with a chunk but not L2 hash
+        let original: Vec<u8> = bytes(5000, 5).iter().map(|b| b % 4).collect();
+        let modified: Vec<u8> = bytes(5000, 6).iter().map(|b| b % 4).collect();
+        let mut sign = Signature::new();
+        sign.reader_to_sign_list(&original[..], 8).unwrap();
+
+        let serial = false_positives_of(&sign, |delta| delta.reader_to_delta_list(&modified[..], 8).unwrap());
+        assert!(serial > 0);
+        for threads in [1, 2, 3, 8] {
+            assert_eq!(false_positives_of(&sign, |delta| {
+                delta.reader_to_delta_list_threaded(&modified[..], 8, threads).unwrap()
+            }), serial, "{}", threads);
+            assert_eq!(false_positives_of(&sign, |delta| {
+                delta.bytes_to_delta_list(&modified, 8, threads).unwrap()
+            }), serial, "{}", threads);
+            // short segments, stitched many times
+            assert_eq!(false_positives_of(&sign, |delta| {
+                delta.slice_to_delta_list::<Adler32>(&modified, 8, threads, 7).unwrap()
+            }), serial, "{}", threads);
+        }
+    }
+
+    #[test]
+    fn ut_readerToDeltaListThreaded_works() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let modified = "sample data for rolling hash difsample data for rolling hash diff.".as_bytes();
+
+        for chunker in [Chunker::Fixed, Chunker::gear(4)] {
+            let mut sign = Signature::with_chunker(chunker);
+            sign.reader_to_sign_list(original, 4).unwrap();
+            for modified in [modified, &original[..3], b"", original] {
+                let mut serial = DiffingDelta::new(&sign);
+                serial.reader_to_delta_list(modified, 4).unwrap();
+                let mut threaded = DiffingDelta::new(&sign);
+                threaded.reader_to_delta_list_threaded(modified, 4, 4).unwrap();
+                assert_eq!(threaded, serial);
+                for threads in [1, 4] {
+                    let mut in_place = DiffingDelta::new(&sign);
+                    in_place.bytes_to_delta_list(modified, 4, threads).unwrap();
+                    assert_eq!(in_place, serial);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ut_withOutput_works() {
+        let original = bytes(20_000, 3);
+        // unmatched run longer than a literal is held
+        let mut modified = bytes(3 * Val::LITERAL_FLUSH_SIZE, 4);
+        modified.extend_from_slice(&original[..10_000]);
+        modified.extend_from_slice(&modified_of(&original, 2));
+
+        let mut sign = Signature::new();
+        sign.reader_to_sign_list(&original[..], 16).unwrap();
+        let mut serial = DiffingDelta::new(&sign);
+        serial.reader_to_delta_list(&modified[..], 16).unwrap();
+
+        for threads in [1, 3] {
+            let mut out = Vec::new();
+            let mut streamed = DiffingDelta::with_output(&sign, &mut out);
+            streamed.reader_to_delta_list_threaded(&modified[..], 16, threads).unwrap();
+            assert!(streamed.list.len() <= 1);
+            assert_eq!(streamed.literal_bytes, serial.literal_bytes);
+            let delta_size = streamed.finish().unwrap();
+            drop(streamed);
+
+            assert_eq!(delta_size, out.len());
+            let delta_file = DeltaFile::decode(&out).unwrap();
+            // same changes, but long literals cut in pieces
+            // (threads pass unmatched bytes on a block at a time)
+            if threads == 1 {
+                assert!(delta_file.list.len() > serial.list.len());
+            }
+            assert_eq!(delta_file.apply(&original).unwrap(), modified);
+        }
+        assert_eq!(serial.finish().unwrap(), DeltaFile::from_delta(&serial).encode().len());
+    }
+
     #[test]
     fn ut_add_get_works() {
         let mut sign = Signature::new();
         sign.add(chunk);
         
-        let mut delta = DiffingDelta::new(&mut sign);
+        let mut delta = DiffingDelta::new(&sign);
         
         assert_eq!(delta.list.len(), 0);
         assert_eq!(delta.list.get(0), None) ;
@@ -230,13 +924,7 @@ mod delta_test {
         assert_ne!(delta.sign.get(0), None);
 
         // ------------- add -------------------
-        let mut ch = Change::new(
-            Some(true),
-            false,
-            None,
-            Some(chunk[0..3].to_owned()), 
-            0
-        );
+        let ch = Change::literal(chunk[0..3].to_owned());
 
         delta.add(ch.clone());
         assert_eq!(delta.len(), 1);
@@ -246,6 +934,15 @@ mod delta_test {
         assert_ne!(_ch, None);
         // ch.is_removed = true;
         assert_eq!(_ch.unwrap(), &ch);
-        
+
+        // adjacent ones end up as one, empty ones are dropped
+        delta.add(Change::literal(chunk[3..].to_owned()));
+        delta.add(Change::literal(Vec::new()));
+        delta.add(Change::copy(0, 5));
+        delta.add(Change::copy(5, 5));
+        assert_eq!(delta.list, vec![
+            Change::literal(chunk.to_owned()),
+            Change::copy(0, 10),
+        ]);
     }
 }
\ No newline at end of file
@@ -0,0 +1,604 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Delta file layout (version 7):
+
+        magic           4 bytes  "RHSD"
+        version         u8
+        chunker id      u8 (Val::CHUNKER_*)
+        gear min, max   varint each (only for gear chunker)
+        strong hash id  u8 (Val::STRONG_HASH_*), L2 hash delta was matched with
+        strong len      u8
+        chunk size      varint
+        original size   u64 (LE)
+        original sum    32 bytes (blake3 of whole original file)
+        changes         change records up to OP_END
+        OP_END          u8
+        modified size   u64 (LE)
+        modified sum    32 bytes (blake3 of whole modified file)
+
+    what modified file is like comes last, so changes can be written
+    while modified file is still being read, see DeltaWriter.
+    each change record is one of:
+
+        OP_COPY         u8
+        offset          varint   (in original file)
+        len             varint
+
+        OP_LITERAL      u8
+        len             varint
+        bytes           len bytes
+
+    patching refuses an original not matching original size/sum
+    and verifies its output against modified size/sum.
+*/
+
+use std::fmt;
+use std::io::{self, Write};
+use super::changes::Change;
+use super::chunker::Chunker;
+use crate::hashing::StrongAlgorithm;
+use super::patch::Patcher;
+use crate::utils::Utility;
+use crate::hashing::blake::Blake3;
+use super::delta::DiffingDelta;
+use super::signature::Signature;
+use crate::fio::FileIO;
+use crate::constants::Val;
+use crate::error::{Error, Result};
+use crate::fio::codec::{Encoder, Decoder};
+
+const OP_COPY: u8 = 0;
+const OP_LITERAL: u8 = 1;
+const OP_END: u8 = 2;
+
+/// Delta as it is stored and sent: list of changes plus what's needed
+/// to reverse them (chunker, chunk size) and to verify the result
+/// (size and checksum of original and modified file).
+#[derive(Debug, PartialEq)]
+pub struct DeltaFile {
+    /// chunker of signature delta was made against
+    pub chunker: Chunker,
+    /// L2 hash delta was matched with, and bytes of it kept
+    pub strong: StrongAlgorithm,
+    pub strong_len: usize,
+    /// chunk size of signature, average one for content defined chunks
+    pub c_size: usize,
+    /// size and blake3 checksum of whole original file
+    pub original_size: usize,
+    pub original_sum: [u8; 32],
+    /// size and blake3 checksum of whole modified file
+    pub modified_size: usize,
+    pub modified_sum: [u8; 32],
+    /// changes in order of modified file
+    pub list: Vec<Change>,
+}
+
+impl DeltaFile {
+    pub fn new(
+        c_size: usize,
+        original: (usize, [u8; 32]),
+        modified: (usize, [u8; 32]),
+        list: Vec<Change>
+    ) -> Self {
+        Self {
+            chunker: Chunker::Fixed,
+            strong: StrongAlgorithm::Xxh3_64,
+            strong_len: StrongAlgorithm::Xxh3_64.digest_len(),
+            c_size,
+            original_size: original.0,
+            original_sum: original.1,
+            modified_size: modified.0,
+            modified_sum: modified.1,
+            list,
+        }
+    }
+
+    // no changes yet and modified file unknown, all a header needs
+    pub fn for_sign(sign: &Signature) -> Self {
+        let mut delta_file = Self::new(
+            sign.c_size,
+            (sign.f_size, sign.f_sum),
+            (0, [0u8; 32]),
+            Vec::new()
+        );
+        delta_file.chunker = sign.chunker;
+        delta_file.strong = sign.strong;
+        delta_file.strong_len = sign.strong_len;
+        delta_file
+    }
+
+    pub fn from_delta(delta: &DiffingDelta) -> Self {
+        let mut delta_file = Self::for_sign(delta.sign);
+        delta_file.modified_size = delta.f_size;
+        delta_file.modified_sum = delta.f_sum;
+        delta_file.list = delta.list.clone();
+        delta_file
+    }
+
+    // checks original, patches it and checks the result
+    pub fn apply(&self, original: &[u8]) -> Result<Vec<u8>> {
+        self.apply_named("original file", original)
+    }
+
+    // name is used for reporting a wrong original
+    fn apply_named(&self, name: &str, original: &[u8]) -> Result<Vec<u8>> {
+        Self::verify(
+            name,
+            (self.original_size, &self.original_sum),
+            original
+        )?;
+        // list must make as many bytes as delta says, checked before allocating them
+        let patcher = Patcher::new(&self.list);
+        let len = patcher.checked_len(original.len())?;
+        if len != self.modified_size {
+            return Err(Error::CorruptDelta(format!(
+                "changes make {} bytes, delta says {}",
+                len,
+                self.modified_size
+            )));
+        }
+        let patched = patcher.apply(original)?;
+        Self::verify(
+            "patched file",
+            (self.modified_size, &self.modified_sum),
+            &patched
+        )?;
+        Ok(patched)
+    }
+
+    // delta turning patched file back into original,
+    // made by diffing original against patched, with same chunker and strong hash
+    pub fn reverse(&self, original: &[u8], patched: &[u8]) -> Result<Self> {
+        let mut sign = Signature::with_chunker(self.chunker);
+        sign.set_strong(self.strong, Some(self.strong_len))?;
+        sign.seed = Utility::random_seed();
+        sign.reader_to_sign_list(patched, self.c_size)?;
+
+        let mut diff_delta = DiffingDelta::new(&sign);
+        diff_delta.reader_to_delta_list(original, self.c_size)?;
+        Ok(Self::from_delta(&diff_delta))
+    }
+
+    // reverse delta is written too, if its path is given
+    pub fn file_to_patched_file(
+        &self,
+        original_path: &str,
+        patched_path: &str,
+        reverse_path: Option<&str>
+    ) -> Result<()> {
+        let original = FileIO::read_file_to_bytes(original_path)?;
+        let patched = self.apply_named(original_path, &original)?;
+        if let Some(reverse_path) = reverse_path {
+            self.reverse(&original, &patched)?.write_to_file(reverse_path)?;
+        }
+        FileIO::write_bytes_to_file(patched_path, &patched)
+    }
+
+    fn verify(what: &str, expected: (usize, &[u8; 32]), bytes: &[u8]) -> Result<()> {
+        if bytes.len() != expected.0 {
+            return Err(Error::ChecksumMismatch {
+                what: what.to_owned(),
+                expected: format!("{} bytes", expected.0),
+                found: format!("{} bytes", bytes.len()),
+            });
+        }
+        let sum = Blake3::sum256(bytes);
+        if &sum != expected.1 {
+            return Err(Error::ChecksumMismatch {
+                what: what.to_owned(),
+                expected: Utility::to_hex(expected.1),
+                found: Utility::to_hex(&sum),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut enc = Encoder::new();
+        self.encode_header(&mut enc);
+        for change in self.list.iter() {
+            Self::encode_op(change, &mut enc);
+            if let Change::Literal(bytes) = change {
+                enc.put_bytes(bytes);
+            }
+        }
+        Self::encode_end((self.modified_size, &self.modified_sum), &mut enc);
+        enc.buf
+    }
+
+    // size of encode() output, without encoding into memory
+    pub fn encoded_len(&self) -> usize {
+        let mut writer = DeltaWriter::new(io::sink(), self);
+        self.list.iter().for_each(|change| writer.put(change));
+        writer.finish(self.modified_size, &self.modified_sum).unwrap_or_default()
+    }
+
+    // everything before changes
+    fn encode_header(&self, enc: &mut Encoder) {
+        enc.put_bytes(&Val::DELTA_MAGIC)
+            .put_u8(Val::DELTA_VERSION);
+        self.chunker.encode(enc);
+        enc.put_u8(self.strong.id()).put_u8(self.strong_len as u8);
+        enc.put_varint(self.c_size as u64)
+            .put_u64(self.original_size as u64)
+            .put_bytes(&self.original_sum);
+    }
+
+    // change record but bytes of a literal
+    fn encode_op(change: &Change, enc: &mut Encoder) {
+        match change {
+            Change::Copy { offset, len } => {
+                enc.put_u8(OP_COPY)
+                    .put_varint(*offset as u64)
+                    .put_varint(*len as u64);
+            }
+            Change::Literal(bytes) => {
+                enc.put_u8(OP_LITERAL)
+                    .put_varint(bytes.len() as u64);
+            }
+        }
+    }
+
+    fn encode_end(modified: (usize, &[u8; 32]), enc: &mut Encoder) {
+        enc.put_u8(OP_END)
+            .put_u64(modified.0 as u64)
+            .put_bytes(modified.1);
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut dec = Decoder::new(bytes);
+        if dec.get_bytes(Val::DELTA_MAGIC.len()) != Some(&Val::DELTA_MAGIC[..]) {
+            return Err(Error::CorruptDelta("not a delta file".to_owned()));
+        }
+        let version = dec.get_u8().unwrap_or_default();
+        if version != Val::DELTA_VERSION {
+            return Err(Error::UnsupportedVersion {
+                what: "delta".to_owned(),
+                found: version,
+                supported: Val::DELTA_VERSION,
+            });
+        }
+        Self::decode_body(&mut dec)
+            .ok_or_else(|| Error::CorruptDelta("truncated or malformed".to_owned()))
+    }
+
+    // everything after version, None means malformed bytes
+    fn decode_body(dec: &mut Decoder) -> Option<Self> {
+        let chunker = Chunker::decode(dec)?;
+        let strong = StrongAlgorithm::from_id(dec.get_u8()?)?;
+        let strong_len = dec.get_u8()? as usize;
+        if !strong.check_len(strong_len) {
+            return None;
+        }
+        let c_size = usize::try_from(dec.get_varint()?).ok()?;
+        let original_size = dec.get_u64()? as usize;
+        let original_sum: [u8; 32] = dec.get_bytes(32)?.try_into().ok()?;
+
+        let mut list = Vec::<Change>::new();
+        loop {
+            let change = match dec.get_u8()? {
+                OP_COPY => Change::copy(
+                    dec.get_varint()? as usize,
+                    dec.get_varint()? as usize
+                ),
+                OP_LITERAL => {
+                    let len = dec.get_varint()? as usize;
+                    Change::literal(dec.get_bytes(len)?.to_owned())
+                }
+                OP_END => break,
+                _ => return None,
+            };
+            list.push(change);
+        }
+        let modified_size = dec.get_u64()? as usize;
+        let modified_sum: [u8; 32] = dec.get_bytes(32)?.try_into().ok()?;
+        // trailing garbage means corrupt file
+        if !dec.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            chunker,
+            strong,
+            strong_len,
+            c_size,
+            original_size,
+            original_sum,
+            modified_size,
+            modified_sum,
+            list,
+        })
+    }
+
+    pub fn write_to_file(&self, path: &str) -> Result<()> {
+        FileIO::write_bytes_to_file(path, &self.encode())
+    }
+
+    pub fn read_from_file(path: &str) -> Result<Self> {
+        Self::decode(&FileIO::read_file_to_bytes(path)?)
+    }
+}
+
+/// Writes a delta file change by change, as changes are found,
+/// so a delta is never held in memory whole.
+///
+/// write errors are kept and reported by `finish`, nothing is written after one.
+pub struct DeltaWriter<W: Write> {
+    out: W,
+    // bytes written so far
+    pub written: usize,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> DeltaWriter<W> {
+    // writes header of delta_file, its changes and modified file are left out
+    pub fn new(out: W, delta_file: &DeltaFile) -> Self {
+        let mut writer = Self {
+            out,
+            written: 0,
+            error: None,
+        };
+        let mut enc = Encoder::new();
+        delta_file.encode_header(&mut enc);
+        writer.write(&enc.buf);
+        writer
+    }
+
+    // literal bytes are written as they are, not copied
+    pub fn put(&mut self, change: &Change) {
+        let mut enc = Encoder::new();
+        DeltaFile::encode_op(change, &mut enc);
+        self.write(&enc.buf);
+        if let Change::Literal(bytes) = change {
+            self.write(bytes);
+        }
+    }
+
+    // ends delta file with size and checksum of modified file,
+    // returns size of whole delta file
+    pub fn finish(mut self, f_size: usize, f_sum: &[u8; 32]) -> Result<usize> {
+        let mut enc = Encoder::new();
+        DeltaFile::encode_end((f_size, f_sum), &mut enc);
+        self.write(&enc.buf);
+        if self.error.is_none() {
+            self.error = self.out.flush().err();
+        }
+        match self.error {
+            Some(e) => Err(e.into()),
+            None => Ok(self.written),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        if self.error.is_some() {
+            return;
+        }
+        match self.out.write_all(bytes) {
+            Ok(()) => self.written += bytes.len(),
+            Err(e) => self.error = Some(e),
+        }
+    }
+}
+
+impl<W: Write> fmt::Debug for DeltaWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeltaWriter")
+            .field("written", &self.written)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+// writers are told apart by what they wrote so far
+impl<W: Write> PartialEq for DeltaWriter<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.written == other.written
+    }
+}
+
+
+#[cfg(test)]
+mod delta_file_test {
+    use super::*;
+
+    fn sample() -> DeltaFile {
+        DeltaFile::new(
+            4,
+            (34, [7u8; 32]),
+            (35, [8u8; 32]),
+            vec![
+                Change::literal("abc".as_bytes().to_owned()),
+                Change::copy(300, 4),
+                Change::literal(Vec::new()),
+            ]
+        )
+    }
+
+    #[test]
+    fn ut_encodeDecode_works() {
+        let delta_file = sample();
+        let bytes = delta_file.encode();
+
+        assert_eq!(bytes[..4], Val::DELTA_MAGIC);
+        assert_eq!(bytes[4], Val::DELTA_VERSION);
+        assert_eq!(DeltaFile::decode(&bytes).unwrap(), delta_file);
+        assert_eq!(delta_file.encoded_len(), bytes.len());
+    }
+
+    #[test]
+    fn ut_encodeDecodeHugeChunk_works() {
+        let mut delta_file = sample();
+        delta_file.c_size = (u32::MAX as usize) + 5;
+        assert_eq!(DeltaFile::decode(&delta_file.encode()).unwrap().c_size, delta_file.c_size);
+    }
+
+    #[test]
+    fn ut_encodeDecodeGear_works() {
+        let mut delta_file = sample();
+        delta_file.chunker = Chunker::gear(4);
+        delta_file.strong = StrongAlgorithm::Blake3;
+        delta_file.strong_len = 20;
+        assert_eq!(DeltaFile::decode(&delta_file.encode()).unwrap(), delta_file);
+    }
+
+    #[test]
+    fn ut_writeReadFile_works() {
+        let path = std::env::temp_dir().join("rhsync_delta_file_test.delta");
+        let path = path.to_str().unwrap();
+        let delta_file = sample();
+
+        assert!(delta_file.write_to_file(path).is_ok());
+        assert_eq!(DeltaFile::read_from_file(path).unwrap(), delta_file);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn ut_decodeBadMagic_fails() {
+        let mut bytes = sample().encode();
+        bytes[0] = b'X';
+        assert!(matches!(DeltaFile::decode(&bytes), Err(Error::CorruptDelta(_))));
+    }
+
+    #[test]
+    fn ut_decodeBadVersion_fails() {
+        let mut bytes = sample().encode();
+        bytes[4] = Val::DELTA_VERSION + 1;
+        assert!(matches!(
+            DeltaFile::decode(&bytes),
+            Err(Error::UnsupportedVersion { found, .. }) if found == Val::DELTA_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn ut_decodeUnknownOp_fails() {
+        let mut bytes = sample().encode();
+        // first record starts right after header
+        bytes[4 + 1 + 1 + 2 + 1 + 8 + 32] = 0xFF;
+        assert!(matches!(DeltaFile::decode(&bytes), Err(Error::CorruptDelta(_))));
+    }
+
+    #[test]
+    fn ut_decodeTruncated_fails() {
+        let bytes = sample().encode();
+        assert!(matches!(
+            DeltaFile::decode(&bytes[..bytes.len() - 1]),
+            Err(Error::CorruptDelta(_))
+        ));
+    }
+
+    #[test]
+    fn ut_apply_works() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let modified = "exle data for rolling hash dif!!".as_bytes();
+        let delta_file = DeltaFile::new(
+            4,
+            (original.len(), Blake3::sum256(original)),
+            (modified.len(), Blake3::sum256(modified)),
+            vec![
+                Change::literal("ex".as_bytes().to_owned()),
+                Change::copy(4, 28),
+                Change::literal("!!".as_bytes().to_owned()),
+            ]
+        );
+        assert_eq!(delta_file.apply(original).unwrap(), modified.to_owned());
+    }
+
+    #[test]
+    fn ut_applyOnWrongOriginal_fails() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let delta_file = DeltaFile::new(
+            4,
+            (original.len(), Blake3::sum256(original)),
+            (original.len(), Blake3::sum256(original)),
+            Vec::new()
+        );
+        // same size, other content
+        assert!(matches!(
+            delta_file.apply("sample data for rolling hash diff!".as_bytes()),
+            Err(Error::ChecksumMismatch { what, .. }) if what == "original file"
+        ));
+        assert!(matches!(
+            delta_file.apply("sample".as_bytes()),
+            Err(Error::ChecksumMismatch { what, .. }) if what == "original file"
+        ));
+    }
+
+    #[test]
+    fn ut_applyWithBadResult_fails() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let delta_file = DeltaFile::new(
+            4,
+            (original.len(), Blake3::sum256(original)),
+            (original.len(), [0u8; 32]),
+            vec![Change::copy(0, original.len())]
+        );
+        assert!(matches!(
+            delta_file.apply(original),
+            Err(Error::ChecksumMismatch { what, .. }) if what == "patched file"
+        ));
+    }
+
+    #[test]
+    fn ut_applyHugeCopy_fails() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let crafted = |list: Vec<Change>, modified_size: usize| {
+            let delta_file = DeltaFile::new(
+                4,
+                (original.len(), Blake3::sum256(original)),
+                (modified_size, [0u8; 32]),
+                list
+            );
+            DeltaFile::decode(&delta_file.encode()).unwrap().apply(original)
+        };
+        // decodes fine and original matches, still no allocation of it
+        assert!(matches!(crafted(vec![Change::copy(0, 1 << 62)], 1 << 62), Err(Error::CorruptDelta(_))));
+        assert!(matches!(
+            crafted(vec![Change::copy(0, 4), Change::copy(usize::MAX - 2, 4)], 8),
+            Err(Error::CorruptDelta(_))
+        ));
+        // copies within original, not as many bytes as delta says
+        assert!(matches!(crafted(vec![Change::copy(0, 4)], 1 << 62), Err(Error::CorruptDelta(_))));
+    }
+
+    #[test]
+    fn ut_reverse_works() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let modified = "sample data foradded rolling hash diff.at the end as well!!".as_bytes();
+
+        let mut sign = Signature::new();
+        sign.reader_to_sign_list(original, 4).unwrap();
+        let mut diff_delta = DiffingDelta::new(&sign);
+        diff_delta.reader_to_delta_list(modified, 4).unwrap();
+        let forward = DeltaFile::from_delta(&diff_delta);
+
+        let patched = forward.apply(original).unwrap();
+        let reverse = forward.reverse(original, &patched).unwrap();
+
+        assert_eq!(reverse.c_size, forward.c_size);
+        assert_eq!(reverse.original_sum, forward.modified_sum);
+        assert_eq!(reverse.modified_sum, forward.original_sum);
+        assert_eq!(reverse.apply(&patched).unwrap(), original.to_owned());
+    }
+
+    #[test]
+    fn ut_reverseGear_works() {
+        let original = "sample data for rolling hash diff.".as_bytes();
+        let modified = "sample data foradded rolling hash diff.at the end as well!!".as_bytes();
+
+        let mut sign = Signature::with_chunker(Chunker::gear(4));
+        sign.set_strong(StrongAlgorithm::Sha256, Some(16)).unwrap();
+        sign.reader_to_sign_list(original, 4).unwrap();
+        let mut diff_delta = DiffingDelta::new(&sign);
+        diff_delta.reader_to_delta_list(modified, 4).unwrap();
+        let forward = DeltaFile::from_delta(&diff_delta);
+
+        let patched = forward.apply(original).unwrap();
+        assert_eq!(patched, modified.to_owned());
+        let reverse = forward.reverse(original, &patched).unwrap();
+        assert_eq!(reverse.chunker, forward.chunker);
+        assert_eq!((reverse.strong, reverse.strong_len), (StrongAlgorithm::Sha256, 16));
+        assert_eq!(reverse.apply(&patched).unwrap(), original.to_owned());
+    }
+}
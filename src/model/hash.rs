@@ -1,24 +1,35 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
 #![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
 
-use crate::constants::{Val};
-use crate::hashing::adler::Adler32;
-use crate::hashing::x2hash::X2Hash64;
+use crate::hashing::{WeakHash, StrongAlgorithm, StrongSum};
 
+/// Weak (L1) and strong (L2) hash of a single chunk.
 #[derive(Debug, PartialEq)]
 pub struct Hash {
-    // level 1 = adler32 rolling hash
+    /// level 1 = rolling hash, adler32 unless chosen otherwise
     pub L1: u32,
-    // level 2 = xx3hash 64 bit non-rolling
-    pub L2: u64,
+    /// level 2 = non-rolling hash, xxh3 64 bit unless chosen otherwise,
+    /// maybe truncated
+    pub L2: StrongSum,
 }
 
 impl Hash {
+    // default hashes, unseeded
     pub fn new(bytes: &[u8]) -> Self {
+        let strong = StrongAlgorithm::Xxh3_64;
+        Self::with(bytes, WeakHash::Adler32, strong, strong.digest_len(), 0)
+    }
+
+    pub fn with(
+        bytes: &[u8],
+        weak: WeakHash,
+        strong: StrongAlgorithm,
+        strong_len: usize,
+        seed: u64
+    ) -> Self {
         Self {
-            L2: X2Hash64::sum64(bytes),
-            L1: Adler32::new().write_bytes(bytes).sum32(),
+            L2: strong.sum(bytes, strong_len, seed),
+            L1: weak.sum32(bytes),
         }
     }
 }
@@ -33,6 +44,11 @@ mod hash_test {
         const chunk: &[u8] = &"chunk".as_bytes();
         let hash = Hash::new(chunk);
         assert_eq!(hash.L1, 104464922);
-        assert_eq!(hash.L2, 8438847523455501592);
+        assert_eq!(hash.L2[..8], 8438847523455501592u64.to_le_bytes());
+
+        let hash = Hash::with(chunk, WeakHash::Buzhash, StrongAlgorithm::Sha256, 12, 5);
+        assert_ne!(hash.L1, 104464922);
+        assert_eq!(hash.L2, StrongAlgorithm::Sha256.sum(chunk, 12, 5));
+        assert_ne!(hash.L2, StrongAlgorithm::Sha256.sum(chunk, 12, 0));
     }
-}
\ No newline at end of file
+}
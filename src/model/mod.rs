@@ -1,7 +1,9 @@
-
 pub mod hash;
+pub mod patch;
 pub mod delta;
+pub mod chunker;
+pub mod delta_file;
 pub mod changes;
 pub mod signature;
-
-
+pub mod unified;
+pub mod report;
@@ -0,0 +1,152 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Patching is a single sequential write of every Change in list order:
+
+        Copy { offset, len }   writes original[offset..offset + len]
+        Literal(bytes)         writes bytes
+
+    a copy reaching past the end of original file means a corrupt delta,
+    every copy is checked before anything is allocated for the output.
+*/
+
+use std::io::Write;
+
+use crate::fio::FileIO;
+use crate::error::{Error, Result};
+use super::changes::Change;
+
+/// Rebuilds modified file from original bytes and a list of changes.
+#[derive(Debug, PartialEq)]
+pub struct Patcher<'local> {
+    pub list: &'local [Change],
+}
+
+impl<'local> Patcher<'local> {
+    pub fn new(list: &'local [Change]) -> Self {
+        Self {
+            list,
+        }
+    }
+
+    pub fn apply(&self, original: &[u8]) -> Result<Vec<u8>> {
+        let len = self.checked_len(original.len())?;
+        let mut patched = Vec::<u8>::with_capacity(len);
+        self.write_to(original, &mut patched)?;
+        Ok(patched)
+    }
+
+    // length of modified file, once every copy is known to be within original
+    pub fn checked_len(&self, original_len: usize) -> Result<usize> {
+        self.list.iter().try_fold(0usize, |total, change| {
+            if let Change::Copy { offset, len } = change {
+                Self::check_copy(*offset, *len, original_len)?;
+            }
+            total.checked_add(change.len()).ok_or_else(
+                || Error::CorruptDelta("length of patched file overflows".to_owned())
+            )
+        })
+    }
+
+    fn check_copy(offset: usize, len: usize, original_len: usize) -> Result<usize> {
+        offset
+            .checked_add(len)
+            .filter(|&end| end <= original_len)
+            .ok_or_else(|| Error::CorruptDelta(format!(
+                "copy of {} bytes at {} is past end of original ({} bytes)",
+                len,
+                offset,
+                original_len
+            )))
+    }
+
+    // writes modified file into any sink, nothing is buffered here
+    pub fn write_to<W: Write>(&self, original: &[u8], writer: &mut W) -> Result<()> {
+        for change in self.list.iter() {
+            match change {
+                Change::Copy { offset, len } => {
+                    let end = Self::check_copy(*offset, *len, original.len())?;
+                    writer.write_all(&original[*offset..end])?;
+                }
+                Change::Literal(bytes) => writer.write_all(bytes)?,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn file_to_patched_file(
+        &self,
+        original_path: &str,
+        patched_path: &str
+    ) -> Result<()> {
+        let original = FileIO::read_file_to_bytes(original_path)?;
+        FileIO::write_bytes_to_file(
+            patched_path,
+            &self.apply(&original)?
+        )
+    }
+}
+
+
+#[cfg(test)]
+mod patch_test {
+    use super::*;
+
+    const original: &[u8] = "sample data for rolling hash diff.".as_bytes();
+
+    #[test]
+    fn ut_applyEmptyList_works() {
+        let patcher = Patcher::new(&[]);
+        assert_eq!(patcher.apply(original).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn ut_applyCopyAndLiteral_works() {
+        let list = vec![
+            // replace "samp" with "ex"
+            Change::literal("ex".as_bytes().to_owned()),
+            // drop "f."
+            Change::copy(4, 28),
+            Change::literal("!!".as_bytes().to_owned()),
+        ];
+        let patcher = Patcher::new(&list);
+        assert_eq!(
+            patcher.apply(original).unwrap(),
+            "exle data for rolling hash dif!!".as_bytes().to_owned()
+        );
+    }
+
+    #[test]
+    fn ut_applyOutOfOrderCopies_works() {
+        let list = vec![
+            Change::copy(7, 4),
+            Change::copy(0, 7),
+            // same bytes twice
+            Change::copy(0, 6),
+        ];
+        let patcher = Patcher::new(&list);
+        assert_eq!(
+            patcher.apply(original).unwrap(),
+            "datasample sample".as_bytes().to_owned()
+        );
+    }
+
+    #[test]
+    fn ut_applyCopyPastEnd_fails() {
+        let list = vec![Change::copy(30, 5)];
+        let patcher = Patcher::new(&list);
+        assert!(matches!(patcher.apply(original), Err(Error::CorruptDelta(_))));
+
+        let list = vec![Change::copy(usize::MAX, 2)];
+        let patcher = Patcher::new(&list);
+        assert!(matches!(patcher.apply(original), Err(Error::CorruptDelta(_))));
+
+        // huge copy is refused before output is allocated
+        let list = vec![Change::literal(vec![1]), Change::copy(0, 1 << 62)];
+        let patcher = Patcher::new(&list);
+        assert!(matches!(patcher.apply(original), Err(Error::CorruptDelta(_))));
+        assert!(matches!(patcher.checked_len(original.len()), Err(Error::CorruptDelta(_))));
+        assert_eq!(Patcher::new(&list[..1]).checked_len(original.len()).unwrap(), 1);
+    }
+}
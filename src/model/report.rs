@@ -0,0 +1,249 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Numbers describing a delta, for dashboards and pipelines.
+
+    matched bytes are copied from original, literal bytes are sent as they are.
+    compression ratio is modified size over encoded delta size,
+    i.e. how many times less a sync transfers than the whole file.
+    false positives are L1 hits L2 didn't confirm, many of them mean
+    weak hash or chunk size suit data poorly.
+*/
+
+use std::fmt;
+use std::time::{Duration, Instant};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde_json::{json, Value};
+use super::changes::Change;
+use super::chunker::Chunker;
+use super::delta::DiffingDelta;
+use super::delta_file::DeltaFile;
+use super::signature::Signature;
+
+#[derive(Debug, PartialEq)]
+pub struct DeltaReport<'a> {
+    pub original_path: &'a str,
+    pub modified_path: &'a str,
+    pub delta: &'a DiffingDelta<'a>,
+    pub matched_bytes: usize,
+    pub literal_bytes: usize,
+    /// chunk matches, a reused chunk counts each time
+    pub matched_chunks: usize,
+    pub deleted_chunks: usize,
+    pub false_positives: usize,
+    /// bytes of delta file
+    pub delta_size: usize,
+}
+
+impl<'a> DeltaReport<'a> {
+    // delta_size as counted while writing, see DiffingDelta::finish
+    pub fn new(
+        delta: &'a DiffingDelta<'a>,
+        delta_size: usize,
+        original_path: &'a str,
+        modified_path: &'a str
+    ) -> Self {
+        Self {
+            original_path,
+            modified_path,
+            delta,
+            matched_bytes: delta.f_size - delta.literal_bytes,
+            literal_bytes: delta.literal_bytes,
+            matched_chunks: delta.uses.iter().sum(),
+            deleted_chunks: delta.deleted_chunks().len(),
+            false_positives: delta.false_positives,
+            delta_size,
+        }
+    }
+
+    pub fn compression_ratio(&self) -> f64 {
+        self.delta.f_size as f64 / self.delta_size as f64
+    }
+
+    fn chunker_json(chunker: &Chunker) -> Value {
+        match *chunker {
+            Chunker::Fixed => json!({ "type": chunker.name() }),
+            Chunker::Gear { min, max } => json!({ "type": chunker.name(), "min": min, "max": max }),
+        }
+    }
+
+    // each op with its offset in modified file, copies with offset in original too
+    fn operations_json(&self) -> Vec<Value> {
+        let mut target = 0;
+        self.delta.list.iter().map(|change| {
+            let op = match change {
+                Change::Copy { offset, len } => json!({
+                    "op": "copy",
+                    "offset": offset,
+                    "target_offset": target,
+                    "len": len,
+                }),
+                Change::Literal(bytes) => json!({
+                    "op": "literal",
+                    "target_offset": target,
+                    "len": bytes.len(),
+                    "data": STANDARD.encode(bytes),
+                }),
+            };
+            target += change.len();
+            op
+        }).collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        let sign = self.delta.sign;
+        let doc = json!({
+            "original": { "path": self.original_path, "size": sign.f_size },
+            "modified": { "path": self.modified_path, "size": self.delta.f_size },
+            "chunk_size": sign.c_size,
+            "chunker": Self::chunker_json(&sign.chunker),
+            "weak_hash": sign.weak.name(),
+            "strong_hash": sign.strong.name(),
+            "operations": self.operations_json(),
+            "summary": {
+                "matched_bytes": self.matched_bytes,
+                "literal_bytes": self.literal_bytes,
+                "matched_chunks": self.matched_chunks,
+                "deleted_chunks": self.deleted_chunks,
+                "false_positives": self.false_positives,
+                "delta_size": self.delta_size,
+                "compression_ratio": self.compression_ratio(),
+            },
+        });
+        serde_json::to_string_pretty(&doc).unwrap_or_default()
+    }
+
+    // size of signature as written to file
+    pub fn sign_stats(sign: &Signature) -> String {
+        format!(
+            "signature size:    {} bytes, {} chunks of {} bytes{}\n",
+            sign.encode().len(),
+            sign.len(),
+            sign.c_size,
+            match sign.chunker {
+                Chunker::Fixed => "",
+                Chunker::Gear { .. } => " on average",
+            }
+        )
+    }
+
+    // size of delta as read from file
+    pub fn patch_stats(delta_file: &DeltaFile) -> String {
+        format!(
+            "delta size:        {} bytes, {} changes for {} bytes of patched file\n",
+            delta_file.encoded_len(),
+            delta_file.list.len(),
+            delta_file.modified_size
+        )
+    }
+
+    pub fn stats(&self) -> String {
+        format!(
+            "{}matched chunks:    {}\n\
+            literal bytes:     {}\n\
+            deleted chunks:    {}\n\
+            delta size:        {} bytes, {:.2}% of {} bytes of modified file\n\
+            false positives:   {} (weak hash hits strong hash didn't confirm)\n",
+            Self::sign_stats(self.delta.sign),
+            self.matched_chunks,
+            self.literal_bytes,
+            self.deleted_chunks,
+            self.delta_size,
+            100.0 * self.delta_size as f64 / self.delta.f_size.max(1) as f64,
+            self.delta.f_size,
+            self.false_positives
+        )
+    }
+}
+
+/// Wall clock time of each step of a run, in order.
+#[derive(Debug, Default)]
+pub struct Phases {
+    pub list: Vec<(&'static str, Duration)>,
+}
+
+impl Phases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn time<T>(&mut self, name: &'static str, step: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = step();
+        self.list.push((name, start.elapsed()));
+        res
+    }
+}
+
+impl fmt::Display for Phases {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, elapsed) in &self.list {
+            writeln!(f, "time {:<13} {:.3} ms", format!("{}:", name), elapsed.as_secs_f64() * 1000.0)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod report_test {
+    use super::*;
+    use crate::{SignOptions, signature};
+
+    #[test]
+    fn ut_toJson_works() {
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(4);
+        let sign = signature(&b"abcdefgh"[..], &opts).unwrap();
+        let mut delta = DiffingDelta::new(&sign);
+        delta.reader_to_delta_list(&b"efghxyabcd"[..], 4).unwrap();
+
+        let report = DeltaReport::new(&delta, delta.encoded_len(), "a.bin", "b.bin");
+        assert_eq!((report.matched_bytes, report.literal_bytes, report.deleted_chunks), (8, 2, 0));
+        assert_eq!(report.matched_chunks, 2);
+
+        let doc: Value = serde_json::from_str(&report.to_json()).unwrap();
+        assert_eq!(doc["original"], json!({ "path": "a.bin", "size": 8 }));
+        assert_eq!(doc["modified"]["size"], 10);
+        assert_eq!(doc["chunk_size"], 4);
+        assert_eq!(doc["chunker"]["type"], "fixed");
+        assert_eq!(doc["operations"], json!([
+            { "op": "copy", "offset": 4, "target_offset": 0, "len": 4 },
+            { "op": "literal", "target_offset": 4, "len": 2, "data": "eHk=" },
+            { "op": "copy", "offset": 0, "target_offset": 6, "len": 4 },
+        ]));
+        assert_eq!(doc["summary"]["delta_size"], report.delta_size);
+        assert_eq!(doc["summary"]["compression_ratio"], 10.0 / report.delta_size as f64);
+    }
+
+    #[test]
+    fn ut_stats_works() {
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(4);
+        let sign = signature(&b"abcdefgh"[..], &opts).unwrap();
+        let mut delta = DiffingDelta::new(&sign);
+        delta.reader_to_delta_list(&b"abcdxyz"[..], 4).unwrap();
+
+        let stats = DeltaReport::new(&delta, delta.encoded_len(), "a", "b").stats();
+        let line = |name: &str| stats.lines().find(|line| line.starts_with(name)).unwrap().to_owned();
+        assert!(line("signature size:").ends_with(" bytes, 2 chunks of 4 bytes"));
+        assert!(line("matched chunks:").ends_with(" 1"));
+        assert!(line("literal bytes:").ends_with(" 3"));
+        assert!(line("deleted chunks:").ends_with(" 1"));
+        assert!(line("delta size:").ends_with(" bytes of modified file"));
+        assert!(line("false positives:").contains(" 0 "));
+
+        let delta_file = DeltaFile::from_delta(&delta);
+        assert_eq!(
+            DeltaReport::patch_stats(&delta_file),
+            format!("delta size:        {} bytes, 2 changes for 7 bytes of patched file\n", delta_file.encode().len())
+        );
+
+        let mut phases = Phases::new();
+        assert_eq!(phases.time("delta", || 42), 42);
+        assert!(phases.to_string().starts_with("time delta:"));
+        assert!(phases.to_string().ends_with(" ms\n"));
+    }
+}
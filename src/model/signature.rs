@@ -1,37 +1,164 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
 #![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Signature file layout (version 5):
+
+        magic           4 bytes  "RHSS"
+        version         u8
+        weak hash id    u8 (Val::WEAK_HASH_*), rolling hash of L1
+        strong hash id  u8 (Val::STRONG_HASH_*), hash of L2
+        strong len      u8, bytes of L2 kept per chunk
+        seed            u64 (LE), strong hash seed, 0 means unseeded
+        chunker id      u8 (Val::CHUNKER_*)
+        gear min, max   varint each (only for gear chunker)
+        chunk size      varint, average one for gear chunker
+        original size   u64 (LE)
+        original sum    32 bytes (blake3 of whole original file)
+        hash count      varint
+        hashes          hash count times L1 u32 (LE) + L2 strong len bytes
+                        + chunk len varint (only for gear chunker)
+*/
 
 use super::hash::Hash;
+use super::chunker::{Chunker, ChunkReader};
 use crate::fio::FileIO;
-use std::collections::BTreeMap;
-use crate::hashing::adler::Adler32;
-use crate::hashing::x2hash::X2Hash64;
+use std::io::Read;
+use std::thread;
+use crate::utils::Utility;
+use crate::constants::Val;
+use crate::error::{Error, Result};
+use crate::fio::codec::{Encoder, Decoder};
+use std::collections::HashMap;
+use crate::hashing::blake::Blake3;
+use crate::traits::RollingHash;
+use crate::hashing::{WeakHash, StrongAlgorithm, StrongSum};
+
+/// What looking a window up in a signature found.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Probe {
+    /// no chunk has its L1 hash
+    Miss,
+    /// some chunk has its L1 hash, none its L2 hash
+    FalsePositive,
+    /// index of chunk having both
+    Match(usize),
+}
 
+impl Probe {
+    pub fn index(self) -> Option<usize> {
+        match self {
+            Self::Match(idx) => Some(idx),
+            _ => None,
+        }
+    }
+}
+
+/// Hashes of every chunk of the original file,
+/// all a delta needs to know about the original.
 #[derive(Debug, PartialEq)]
 pub struct Signature {
+    /// size of chunks, average one for content defined chunks
+    pub c_size: usize,
+    /// how original file is cut into chunks
+    pub chunker: Chunker,
+    /// L1 hash of chunks
+    pub weak: WeakHash,
+    /// L2 hash of chunks
+    pub strong: StrongAlgorithm,
+    /// L2 is truncated to this many bytes
+    pub strong_len: usize,
+    /// strong hash seed, random per signature so that chunks
+    /// colliding on purpose can't be crafted in advance
+    pub seed: u64,
+    /// size of whole original file
+    pub f_size: usize,
+    /// blake3 checksum of whole original file
+    pub f_sum: [u8; 32],
+    /// hashes of every chunk, in file order
     pub list: Vec<Hash>,
-    // used to prevent duplicate chunks
-    // this happens when chunk chars are same 
-    // for multiple chunks in original file 
-    pub traced: BTreeMap<usize, bool>,
+    /// end offset of every chunk in original file
+    pub ends: Vec<usize>,
+    /// L1 hash -> indexes (ascending) of chunks in list having it
+    /// keep list and index in sync by adding through add/push only
+    pub index: HashMap<u32, Vec<usize>>,
+    /// 16-bit tag bitmap of all L1 hashes in list
+    /// cheap pre-filter before looking up index, like rsync does
+    pub tags: Vec<u64>,
 }
 
 impl Signature {
     pub fn new() -> Self {
+        Self::with_chunker(Chunker::Fixed)
+    }
+
+    pub fn with_chunker(chunker: Chunker) -> Self {
         Self {
+            c_size: 0,
+            chunker,
+            weak: WeakHash::Adler32,
+            strong: StrongAlgorithm::Xxh3_64,
+            strong_len: StrongAlgorithm::Xxh3_64.digest_len(),
+            seed: 0,
+            f_size: 0,
+            f_sum: [0u8; 32],
             list: Vec::new(),
-            traced: BTreeMap::new(),
+            ends: Vec::new(),
+            index: HashMap::new(),
+            tags: vec![0u64; 1 << 10],
         }
     }
 
     pub fn add(&mut self, bytes: &[u8]) {
-        self.list.push(
+        self.push(
             // save as hash object
-            Hash::new(bytes)
+            self.hash_of(bytes),
+            bytes.len()
         );
     }
 
+    // hash of a chunk as this signature makes it
+    pub fn hash_of(&self, bytes: &[u8]) -> Hash {
+        Hash::with(bytes, self.weak, self.strong, self.strong_len, self.seed)
+    }
+
+    // picks strong hash, len None means whole digest
+    pub fn set_strong(&mut self, strong: StrongAlgorithm, len: Option<usize>) -> Result<()> {
+        let len = len.unwrap_or(strong.digest_len());
+        if !strong.check_len(len) {
+            return Err(Error::Usage(format!(
+                "strong hash length of {} must be within {}..={}, got {}",
+                strong.name(),
+                Val::MIN_STRONG_LEN,
+                strong.digest_len(),
+                len
+            )));
+        }
+        self.strong = strong;
+        self.strong_len = len;
+        Ok(())
+    }
+
+    pub fn push(&mut self, hash: Hash, len: usize) {
+        let tag = Self::tag_of(hash.L1);
+        self.tags[tag >> 6] |= 1 << (tag & 63);
+        self.index.entry(hash.L1).or_default().push(self.list.len());
+        self.list.push(hash);
+        self.ends.push(self.ends.last().copied().unwrap_or_default() + len);
+    }
+
+    // where chunk at index starts in original file
+    pub fn offset_of(&self, index: usize) -> usize {
+        match index {
+            0 => 0,
+            i => self.ends[i - 1],
+        }
+    }
+
+    pub fn len_of(&self, index: usize) -> usize {
+        self.ends[index] - self.offset_of(index)
+    }
+
     pub fn get(&self, index: usize) -> Option<&Hash> {
         self.list.get(index)
     }
@@ -40,22 +167,66 @@ impl Signature {
         self.list.len()
     }
 
-    pub fn try_get_position_of(&mut self, adler: &Adler32) -> Option<usize> {
-        for (i, hash) in self.list.iter().enumerate() {
-            if hash.L1 == adler.sum32() {
-                // L1 hash matches;
-                if 
-                    hash.L2 == X2Hash64::sum64(&adler.window[..]) &&
-                    // check if its a duplicate chunk already matched! 
-                    !self.traced.contains_key(&i) {
-                    // L2 hash matches;
-                    self.traced.insert(i, true);
-                    return Some(i);
-                }
-            }
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    // any chunk may be matched any number of times,
+    // among equal chunks the one at preferred index wins
+    // so that a run of chunks is matched as one piece
+    // roller must be of signature's weak hash
+    pub(crate) fn probe_position_of<H: RollingHash>(
+        &self,
+        roller: &mut H,
+        preferred: usize
+    ) -> Probe {
+        // L2 is computed only on L1 hit
+        let l1 = roller.digest();
+        self.lookup(
+            l1,
+            || self.strong.sum(roller.window(), self.strong_len, self.seed),
+            preferred
+        )
+    }
+
+    // same as probe_position_of for a whole chunk already hashed
+    pub(crate) fn probe(&self, hash: &Hash, preferred: usize) -> Probe {
+        self.lookup(hash.L1, || hash.L2, preferred)
+    }
+
+    // index of chunk having hash
+    pub fn find(&self, hash: &Hash, preferred: usize) -> Option<usize> {
+        self.probe(hash, preferred).index()
+    }
+
+    fn lookup(
+        &self,
+        l1: u32,
+        l2: impl FnOnce() -> StrongSum,
+        preferred: usize
+    ) -> Probe {
+        let tag = Self::tag_of(l1);
+        if self.tags[tag >> 6] & (1 << (tag & 63)) == 0 {
+            return Probe::Miss;
+        }
+        // L1 hash matches;
+        let Some(candidates) = self.index.get(&l1) else {
+            return Probe::Miss;
+        };
+        let l2 = l2();
+        if candidates.binary_search(&preferred).is_ok() && self.list[preferred].L2 == l2 {
+            return Probe::Match(preferred);
+        }
+        // L2 hash matches;
+        match candidates.iter().copied().find(|&i| self.list[i].L2 == l2) {
+            Some(idx) => Probe::Match(idx),
+            None => Probe::FalsePositive,
         }
-        return None;
-    } 
+    }
+
+    fn tag_of(l1: u32) -> usize {
+        ((l1 >> 16) ^ (l1 & 0xFFFF)) as usize
+    }
 
     // convert file bytes to chunks and then list of hash objects
     pub fn file_to_sign_list(
@@ -63,29 +234,259 @@ impl Signature {
         path: &str,
         c_size: usize,
         f_size: usize
-    ) -> Option<()>{
-        // get list of chunks from file bytes (read internally)
-        let chunk_list = FileIO::read_file_to_chunk_list(
-            path, 
-            c_size, 
-            f_size
-        );
-        if chunk_list.is_none() { return None; }
-        
-        let chunk_list = chunk_list.unwrap();
+    ) -> Result<()>{
+        let reader = FileIO::open_file_reader(path)?;
+        if c_size > 0 {
+            self.list.reserve(Utility::get_num_of_chunks(f_size, c_size));
+        }
+        self.reader_to_sign_list(reader, c_size)
+            .map_err(|e| e.with_path(path))
+    }
 
-        for chunk in chunk_list.into_iter() {
-            // this adds chunk as hash object internally
-            self.add(&chunk[..]);
+    // same as above but for any byte stream,
+    // only a bounded buffer of it is held in memory
+    pub fn reader_to_sign_list<R: Read>(
+        &mut self,
+        reader: R,
+        c_size: usize
+    ) -> Result<()> {
+        self.reader_to_sign_list_threaded(reader, c_size, 1)
+    }
+
+    // same as above with chunks hashed by that many threads,
+    // signature is the same whatever the number of threads
+    pub fn reader_to_sign_list_threaded<R: Read>(
+        &mut self,
+        reader: R,
+        c_size: usize,
+        threads: usize
+    ) -> Result<()> {
+        self.chunker.check(c_size)?;
+        let threads = threads.max(1);
+        let mut blake = Blake3::new();
+        let buf_len = Val::READ_BUF_SIZE.max(threads * Val::THREAD_BATCH_SIZE);
+        let mut chunks = ChunkReader::with_buf_len(reader, self.chunker, c_size, buf_len);
+        self.c_size = c_size;
+        self.f_size = 0;
+
+        loop {
+            let batch = chunks.next_chunks()?;
+            if batch.is_empty() {
+                break;
+            }
+            self.add_batch(&batch, threads, &mut blake);
+        }
+        self.f_sum = blake.digest();
+        Ok(())
+    }
+
+    // same as above for a whole file in memory (e.g. memory mapped),
+    // chunks are cut in place, not copied
+    pub fn bytes_to_sign_list(
+        &mut self,
+        bytes: &[u8],
+        c_size: usize,
+        threads: usize
+    ) -> Result<()> {
+        self.chunker.check(c_size)?;
+        let threads = threads.max(1);
+        let mut blake = Blake3::new();
+        let mut chunks = self.chunker.chunks(bytes, c_size);
+        self.c_size = c_size;
+        self.f_size = 0;
+
+        loop {
+            let batch = Chunker::take_batch(&mut chunks, threads * Val::THREAD_BATCH_SIZE);
+            if batch.is_empty() {
+                break;
+            }
+            self.add_batch(&batch, threads, &mut blake);
+        }
+        self.f_sum = blake.digest();
+        Ok(())
+    }
+
+    fn add_batch(&mut self, batch: &[&[u8]], threads: usize, blake: &mut Blake3) {
+        let hashes = self.hash_batch(batch, threads, blake);
+        for (chunk, hash) in batch.iter().zip(hashes) {
+            self.f_size += chunk.len();
+            self.push(hash, chunk.len());
+        }
+    }
+
+    // hashes of chunks in their order, each thread takes
+    // a run of them while whole file sum is updated meanwhile
+    pub(crate) fn hash_batch(&self, batch: &[&[u8]], threads: usize, blake: &mut Blake3) -> Vec<Hash> {
+        if threads == 1 {
+            batch.iter().for_each(|chunk| { blake.write_bytes(chunk); });
+            return batch.iter().map(|chunk| self.hash_of(chunk)).collect();
+        }
+        let per_thread = batch.len().div_ceil(threads);
+        thread::scope(|scope| {
+            let workers: Vec<_> = batch.chunks(per_thread).map(|run| {
+                scope.spawn(move || {
+                    run.iter().map(|chunk| self.hash_of(chunk)).collect::<Vec<Hash>>()
+                })
+            }).collect();
+            batch.iter().for_each(|chunk| { blake.write_bytes(chunk); });
+            workers.into_iter()
+                .flat_map(|worker| worker.join().expect("hashing thread panicked"))
+                .collect()
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut enc = Encoder::new();
+        enc.put_bytes(&Val::SIGN_MAGIC)
+            .put_u8(Val::SIGN_VERSION)
+            .put_u8(self.weak.id())
+            .put_u8(self.strong.id())
+            .put_u8(self.strong_len as u8)
+            .put_u64(self.seed);
+        self.chunker.encode(&mut enc);
+        enc.put_varint(self.c_size as u64)
+            .put_u64(self.f_size as u64)
+            .put_bytes(&self.f_sum)
+            .put_varint(self.list.len() as u64);
+
+        let gear = self.chunker != Chunker::Fixed;
+        for (i, hash) in self.list.iter().enumerate() {
+            enc.put_u32(hash.L1).put_bytes(&hash.L2[..self.strong_len]);
+            if gear {
+                enc.put_varint(self.len_of(i) as u64);
+            }
+        }
+        enc.buf
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut dec = Decoder::new(bytes);
+        if dec.get_bytes(Val::SIGN_MAGIC.len()) != Some(&Val::SIGN_MAGIC[..]) {
+            return Err(Error::CorruptSignature("not a signature file".to_owned()));
+        }
+        let version = dec.get_u8().unwrap_or_default();
+        if version != Val::SIGN_VERSION {
+            return Err(Error::UnsupportedVersion {
+                what: "signature".to_owned(),
+                found: version,
+                supported: Val::SIGN_VERSION,
+            });
+        }
+        Self::decode_body(&mut dec, bytes.len())
+            .ok_or_else(|| Error::CorruptSignature("truncated or malformed".to_owned()))?
+    }
+
+    // everything after version, None means malformed bytes
+    fn decode_body(dec: &mut Decoder, len: usize) -> Option<Result<Self>> {
+        // only hashes we are able to recompute are accepted
+        let weak = dec.get_u8()?;
+        let strong = dec.get_u8()?;
+        let (weak, strong) = match (WeakHash::from_id(weak), StrongAlgorithm::from_id(strong)) {
+            (Some(weak), Some(strong)) => (weak, strong),
+            _ => return Some(Err(Error::CorruptSignature(
+                format!("unknown hash algorithms {}/{}", weak, strong)
+            ))),
+        };
+        let strong_len = dec.get_u8()? as usize;
+        if !strong.check_len(strong_len) {
+            return None;
+        }
+        let seed = dec.get_u64()?;
+        let mut sign = Self::with_chunker(Chunker::decode(dec)?);
+        sign.weak = weak;
+        sign.strong = strong;
+        sign.strong_len = strong_len;
+        sign.seed = seed;
+        sign.c_size = usize::try_from(dec.get_varint()?).ok()?;
+        sign.f_size = dec.get_u64()? as usize;
+        sign.f_sum = dec.get_bytes(32)?.try_into().ok()?;
+
+        let count = dec.get_varint()? as usize;
+        // fixed chunks cover file exactly, no empty ones at the end
+        let fixed_count = match (sign.f_size, sign.c_size) {
+            (0, _) => Some(0),
+            (_, 0) => None,
+            (f_size, c_size) => Some(f_size.div_ceil(c_size)),
+        };
+        if sign.chunker == Chunker::Fixed && fixed_count != Some(count) {
+            return Some(Err(Error::CorruptSignature(format!(
+                "{} chunks of {} bytes for file of {} bytes",
+                count,
+                sign.c_size,
+                sign.f_size
+            ))));
+        }
+        if count > 0 && sign.chunker.check(sign.c_size).is_err() {
+            return None;
+        }
+        // don't trust count for allocation
+        sign.list.reserve(count.min(len / (4 + strong_len)));
+        for i in 0..count {
+            let L1 = dec.get_u32()?;
+            let mut L2 = [0u8; Val::MAX_STRONG_LEN];
+            L2[..strong_len].copy_from_slice(dec.get_bytes(strong_len)?);
+            let hash = Hash { L1, L2 };
+            let len = match sign.chunker {
+                // fixed chunks need no lengths, only last one may be short
+                Chunker::Fixed => sign.c_size.min(sign.f_size.checked_sub(i * sign.c_size)?),
+                // cut between min and max, only last one may be short
+                Chunker::Gear { min, max } => {
+                    let len = usize::try_from(dec.get_varint()?).unwrap_or(usize::MAX);
+                    if len == 0 || len > max || (len < min && i + 1 < count) {
+                        return Some(Err(Error::CorruptSignature(format!(
+                            "chunk {} of {} bytes, gear chunks are {}..={} bytes",
+                            i,
+                            len,
+                            min,
+                            max
+                        ))));
+                    }
+                    len
+                }
+            };
+            let end = sign.ends.last().copied().unwrap_or_default().checked_add(len);
+            if end.is_none_or(|end| end > sign.f_size) {
+                return Some(Err(Error::CorruptSignature(format!(
+                    "chunks run past end of file of {} bytes",
+                    sign.f_size
+                ))));
+            }
+            sign.push(hash, len);
+        }
+        if !dec.is_empty() {
+            return None;
+        }
+        let covered = sign.ends.last().copied().unwrap_or_default();
+        if covered != sign.f_size {
+            return Some(Err(Error::CorruptSignature(format!(
+                "chunks cover {} bytes of file of {} bytes",
+                covered,
+                sign.f_size
+            ))));
         }
-        Some(())
+        Some(Ok(sign))
     }
 
+    pub fn write_to_file(&self, path: &str) -> Result<()> {
+        FileIO::write_bytes_to_file(path, &self.encode())
+    }
+
+    pub fn read_from_file(path: &str) -> Result<Self> {
+        Self::decode(&FileIO::read_file_to_bytes(path)?)
+    }
+}
+
+impl Default for Signature {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
 mod sign_test {
     use super::*;
+    use crate::hashing::adler::Adler32;
+    use crate::hashing::buzhash::Buzhash;
     
     const chunk: &[u8] = &"chunk".as_bytes();
 
@@ -105,9 +506,256 @@ mod sign_test {
         let hash = sign.get(0).unwrap();
         
         assert_eq!(hash.L1, 104464922);
-        assert_eq!(hash.L2, 8438847523455501592);
+        assert_eq!(hash.L2[..8], 8438847523455501592u64.to_le_bytes());
 
         assert_eq!(sign.get(1), None);
     }
+
+    #[test]
+    fn ut_tryGetPositionOf_works() {
+        let mut sign = Signature::new();
+        sign.add(chunk);
+        sign.add("other".as_bytes());
+        sign.add(chunk);
+
+        assert_eq!(sign.index.get(&sign.list[0].L1), Some(&vec![0, 2]));
+
+        let mut adler = Adler32::new();
+        for byte in chunk.iter() {
+            adler.roll_in(*byte);
+        }
+        // same chunk can be matched again and again
+        assert_eq!(sign.probe_position_of(&mut adler, 0), Probe::Match(0));
+        assert_eq!(sign.probe_position_of(&mut adler, 0), Probe::Match(0));
+        // preferred one among equal chunks
+        assert_eq!(sign.probe_position_of(&mut adler, 2), Probe::Match(2));
+        // preferred one is no match, first one then
+        assert_eq!(sign.probe_position_of(&mut adler, 1), Probe::Match(0));
+        assert_eq!(sign.probe_position_of(&mut adler, 7), Probe::Match(0));
+
+        let mut adler = Adler32::new();
+        for byte in "chunx".as_bytes().iter() {
+            adler.roll_in(*byte);
+        }
+        assert_eq!(sign.probe_position_of(&mut adler, 0), Probe::Miss);
+
+        // other weak hash, same matching
+        let mut sign = Signature::new();
+        sign.weak = WeakHash::Buzhash;
+        sign.add("other".as_bytes());
+        sign.add(chunk);
+        let mut buz = Buzhash::new();
+        for byte in "xchunk".as_bytes().iter() {
+            buz.roll_in(*byte);
+        }
+        buz.roll_out();
+        assert_eq!(sign.probe_position_of(&mut buz, 0), Probe::Match(1));
+    }
+
+    #[test]
+    fn ut_falsePositives_works() {
+        let mut sign = Signature::new();
+        sign.add(chunk);
+        assert_eq!(sign.probe(&sign.hash_of(chunk), 0), Probe::Match(0));
+
+        // same L1, other L2
+        let mut hash = sign.hash_of(chunk);
+        hash.L2[0] ^= 1;
+        assert_eq!(sign.probe(&hash, 0), Probe::FalsePositive);
+        assert_eq!(sign.find(&hash, 0), None);
+        // no L1 hit, no false positive either
+        assert_eq!(sign.probe(&sign.hash_of(b"chunx"), 0), Probe::Miss);
+    }
+
+    #[test]
+    fn ut_readerToSignList_works() {
+        let bytes = "sample data for rolling hash diff.".as_bytes();
+        let mut sign = Signature::new();
+        sign.reader_to_sign_list(bytes, 4).unwrap();
+
+        assert_eq!(sign.len(), 9);
+        assert_eq!(sign.c_size, 4);
+        assert_eq!(sign.f_size, bytes.len());
+        assert_eq!(sign.f_sum, Blake3::sum256(bytes));
+        assert_eq!(sign.get(8), Some(&Hash::new("f.".as_bytes())));
+    }
+
+    #[test]
+    fn ut_readerToSignListThreaded_works() {
+        let bytes: Vec<u8> = (0..3_000_000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        for chunker in [Chunker::Fixed, Chunker::gear(700)] {
+            let mut single = Signature::with_chunker(chunker);
+            single.reader_to_sign_list(&bytes[..], 700).unwrap();
+            for threads in [2, 3, 8] {
+                let mut multi = Signature::with_chunker(chunker);
+                multi.reader_to_sign_list_threaded(&bytes[..], 700, threads).unwrap();
+                assert_eq!(multi, single);
+                assert_eq!(multi.encode(), single.encode());
+
+                let mut in_place = Signature::with_chunker(chunker);
+                in_place.bytes_to_sign_list(&bytes, 700, threads).unwrap();
+                assert_eq!(in_place, single);
+            }
+        }
+    }
+
+    #[test]
+    fn ut_encodeDecode_works() {
+        let mut sign = Signature::new();
+        sign.c_size = 5;
+        sign.f_size = 10;
+        sign.f_sum = [3u8; 32];
+        sign.add(chunk);
+        sign.add("other".as_bytes());
+
+        let bytes = sign.encode();
+        assert_eq!(bytes[..4], Val::SIGN_MAGIC);
+        assert_eq!(Signature::decode(&bytes).unwrap(), sign);
+
+        sign.weak = WeakHash::RabinKarp;
+        assert_eq!(Signature::decode(&sign.encode()).unwrap().weak, WeakHash::RabinKarp);
+    }
+
+    #[test]
+    fn ut_seed_works() {
+        let bytes = "sample data for rolling hash diff.".as_bytes();
+        let mut sign = Signature::new();
+        sign.seed = 0x5eed;
+        sign.reader_to_sign_list(bytes, 4).unwrap();
+
+        let decoded = Signature::decode(&sign.encode()).unwrap();
+        assert_eq!(decoded.seed, 0x5eed);
+        assert_eq!(decoded, sign);
+        // seeded L2 differs from unseeded one, L1 doesn't
+        assert_eq!(sign.get(0).unwrap().L1, Hash::new(b"samp").L1);
+        assert_ne!(sign.get(0).unwrap().L2, Hash::new(b"samp").L2);
+
+        // chunks are found with the seed they were hashed with
+        let mut adler = Adler32::new();
+        for byte in "le d".as_bytes().iter() {
+            adler.roll_in(*byte);
+        }
+        assert_eq!(sign.probe_position_of(&mut adler, 0), Probe::Match(1));
+        assert_eq!(sign.find(&sign.hash_of(b"le d"), 0), Some(1));
+        assert_eq!(sign.find(&Hash::new(b"le d"), 0), None);
+    }
+
+    #[test]
+    fn ut_encodeDecodeStrongHash_works() {
+        let bytes = "sample data for rolling hash diff.".as_bytes();
+        let mut sign = Signature::new();
+        sign.set_strong(StrongAlgorithm::Sha256, Some(6)).unwrap();
+        sign.reader_to_sign_list(bytes, 4).unwrap();
+
+        assert_eq!(sign.get(0).unwrap().L2, StrongAlgorithm::Sha256.sum(b"samp", 6, 0));
+        let encoded = sign.encode();
+        assert_eq!(Signature::decode(&encoded).unwrap(), sign);
+
+        // only 6 bytes of L2 are stored per chunk
+        let mut full = Signature::new();
+        full.set_strong(StrongAlgorithm::Sha256, None).unwrap();
+        assert_eq!(full.strong_len, 32);
+        full.reader_to_sign_list(bytes, 4).unwrap();
+        assert_eq!(full.encode().len() - encoded.len(), sign.len() * (32 - 6));
+
+        assert!(matches!(
+            sign.set_strong(StrongAlgorithm::Xxh3_64, Some(9)),
+            Err(Error::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn ut_encodeDecodeGear_works() {
+        let bytes = "sample data for rolling hash diff.".as_bytes();
+        let mut sign = Signature::with_chunker(Chunker::gear(4));
+        sign.reader_to_sign_list(bytes, 4).unwrap();
+
+        assert_eq!(sign.ends.last(), Some(&bytes.len()));
+        assert!((0..sign.len()).all(|i| (1..=16).contains(&sign.len_of(i))));
+        assert_eq!(Signature::decode(&sign.encode()).unwrap(), sign);
+    }
+
+    #[test]
+    fn ut_decodeGearLengths_fails() {
+        // gear signature of f_size bytes with chunks of given lengths
+        fn gear_sign(f_size: usize, lens: &[u64]) -> Vec<u8> {
+            let mut sign = Signature::with_chunker(Chunker::Gear { min: 2, max: 8 });
+            sign.c_size = 4;
+            sign.f_size = f_size;
+            let mut bytes = sign.encode();
+            // no chunks, count is last byte
+            bytes.pop();
+            let mut enc = Encoder::new();
+            enc.put_varint(lens.len() as u64);
+            for len in lens {
+                enc.put_u32(0).put_bytes(&vec![0u8; sign.strong_len]).put_varint(*len);
+            }
+            bytes.extend(enc.buf);
+            bytes
+        }
+
+        assert!(Signature::decode(&gear_sign(10, &[5, 5])).is_ok());
+        // only last chunk may be shorter than min
+        assert!(Signature::decode(&gear_sign(9, &[8, 1])).is_ok());
+
+        for (f_size, lens) in [
+            (10, &[u64::MAX, 5][..]),
+            (10, &[0, 5, 5]),
+            (10, &[9, 1]),
+            (10, &[1, 5, 4]),
+            (10, &[5, 4]),
+            (10, &[5, 5, 2]),
+        ] {
+            assert!(
+                matches!(Signature::decode(&gear_sign(f_size, lens)), Err(Error::CorruptSignature(_))),
+                "{:?}",
+                lens
+            );
+        }
+    }
+
+    #[test]
+    fn ut_decodeCorrupt_fails() {
+        let mut sign = Signature::new();
+        sign.add(chunk);
+        let bytes = sign.encode();
+
+        assert!(matches!(
+            Signature::decode(&bytes[..bytes.len() - 1]),
+            Err(Error::CorruptSignature(_))
+        ));
+
+        let mut bad_algo = bytes.clone();
+        bad_algo[6] = 0xFF;
+        assert!(matches!(
+            Signature::decode(&bad_algo),
+            Err(Error::CorruptSignature(_))
+        ));
+
+        // fixed chunks past end of file
+        let mut sign = Signature::new();
+        sign.reader_to_sign_list(&b"abcdefgh"[..], 4).unwrap();
+        sign.push(Hash::new(b""), 0);
+        assert!(matches!(
+            Signature::decode(&sign.encode()),
+            Err(Error::CorruptSignature(_))
+        ));
+
+        // empty file has no chunks, not one empty chunk
+        let mut sign = Signature::new();
+        sign.c_size = 4;
+        sign.push(Hash::new(b""), 0);
+        assert!(matches!(
+            Signature::decode(&sign.encode()),
+            Err(Error::CorruptSignature(_))
+        ));
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = Val::SIGN_VERSION + 1;
+        assert!(matches!(
+            Signature::decode(&bad_version),
+            Err(Error::UnsupportedVersion { .. })
+        ));
+    }
 }
 
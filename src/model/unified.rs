@@ -0,0 +1,340 @@
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+/*
+    Unified diff (diff -u) view of a delta between two text files.
+
+    a line of modified file is kept when delta copies it whole, in one go,
+    from the start of a line of original file. kept lines must come in
+    original order, so lines copied out of order show as removed and added.
+    lines in between come from literals, e.g. all of a file smaller than
+    one chunk, and are matched by longest common subsequence of lines,
+    unless the gap is too big for it.
+*/
+
+use std::ops::Range;
+use crate::constants::Val;
+use crate::error::Result;
+use crate::model::changes::Change;
+use crate::model::patch::Patcher;
+
+// largest gap, in lines of original times lines of modified,
+// matched line by line
+const MAX_GAP_CELLS: usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LineOp {
+    // line of original, line of modified
+    Keep(usize, usize),
+    Remove(usize),
+    Add(usize),
+}
+
+pub struct UnifiedDiff<'a> {
+    original: &'a [u8],
+    list: &'a [Change],
+    modified: Vec<u8>,
+    // start of each line, and end of last one
+    o_lines: Vec<usize>,
+    m_lines: Vec<usize>,
+    /// lines of unchanged text around changes
+    pub context: usize,
+}
+
+impl<'a> UnifiedDiff<'a> {
+    // modified file is rebuilt from original and delta list,
+    // copies past end of original make it Error::CorruptDelta
+    pub fn new(original: &'a [u8], list: &'a [Change]) -> Result<Self> {
+        let modified = Patcher::new(list).apply(original)?;
+        Ok(Self {
+            original,
+            list,
+            o_lines: Self::line_starts(original),
+            m_lines: Self::line_starts(&modified),
+            modified,
+            context: Val::UNIFIED_CONTEXT,
+        })
+    }
+
+    fn line_starts(bytes: &[u8]) -> Vec<usize> {
+        let mut starts = vec![0];
+        starts.extend(
+            bytes.iter().enumerate().filter(|(_, b)| **b == b'\n').map(|(i, _)| i + 1)
+        );
+        // no empty last line after a trailing newline
+        if *starts.last().unwrap() != bytes.len() {
+            starts.push(bytes.len());
+        }
+        starts
+    }
+
+    fn is_binary(&self) -> bool {
+        self.original.contains(&0) || self.modified.contains(&0)
+    }
+
+    fn o_line(&self, i: usize) -> &[u8] {
+        &self.original[self.o_lines[i]..self.o_lines[i + 1]]
+    }
+
+    fn m_line(&self, j: usize) -> &[u8] {
+        &self.modified[self.m_lines[j]..self.m_lines[j + 1]]
+    }
+
+    // line of original each line of modified is copied from, if any
+    fn copied_lines(&self) -> Vec<Option<usize>> {
+        // where each copy lands in modified, and where it's from
+        let mut copies = Vec::new();
+        let mut pos = 0;
+        for change in self.list {
+            if let Change::Copy { offset, len } = change {
+                copies.push((pos, *offset, *len));
+            }
+            pos += change.len();
+        }
+
+        let mut copied = Vec::with_capacity(self.m_lines.len() - 1);
+        let mut k = 0;
+        for j in 0..self.m_lines.len() - 1 {
+            let (start, end) = (self.m_lines[j], self.m_lines[j + 1]);
+            while k < copies.len() && copies[k].0 + copies[k].2 <= start {
+                k += 1;
+            }
+            // copies covering whole line must follow on in original too
+            let mut from = None;
+            let mut at = start;
+            for &(m_pos, o_pos, len) in &copies[k..] {
+                // literal in between
+                if m_pos > at {
+                    break;
+                }
+                let o_at = o_pos + (at - m_pos);
+                match from {
+                    None => from = Some(o_at),
+                    Some(f) if f + (at - start) != o_at => break,
+                    _ => {}
+                }
+                at = m_pos + len;
+                if at >= end {
+                    break;
+                }
+            }
+            copied.push(match from {
+                Some(o_start) if at >= end => self.o_lines.binary_search(&o_start).ok()
+                    .filter(|&i| i + 1 < self.o_lines.len() && self.o_lines[i + 1] - o_start == end - start),
+                _ => None,
+            });
+        }
+        copied
+    }
+
+    fn line_ops(&self) -> Vec<LineOp> {
+        let o_count = self.o_lines.len() - 1;
+        let m_count = self.m_lines.len() - 1;
+
+        // greedily keep copied lines in original order
+        let mut kept = Vec::new();
+        for (j, from) in self.copied_lines().into_iter().enumerate() {
+            if let Some(i) = from {
+                if kept.last().is_none_or(|&(last, _)| i > last) {
+                    kept.push((i, j));
+                }
+            }
+        }
+        kept.push((o_count, m_count));
+
+        let mut ops = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        for (o_end, m_end) in kept {
+            self.gap_ops(i..o_end, j..m_end, &mut ops);
+            if o_end < o_count {
+                ops.push(LineOp::Keep(o_end, m_end));
+            }
+            (i, j) = (o_end + 1, m_end + 1);
+        }
+        ops
+    }
+
+    // lines between kept ones, equal lines at both ends are cheap to keep
+    fn gap_ops(&self, o: Range<usize>, m: Range<usize>, ops: &mut Vec<LineOp>) {
+        let mut head = 0;
+        while o.start + head < o.end && m.start + head < m.end
+            && self.o_line(o.start + head) == self.m_line(m.start + head) {
+            head += 1;
+        }
+        let mut tail = 0;
+        while o.end - tail > o.start + head && m.end - tail > m.start + head
+            && self.o_line(o.end - tail - 1) == self.m_line(m.end - tail - 1) {
+            tail += 1;
+        }
+        ops.extend((0..head).map(|n| LineOp::Keep(o.start + n, m.start + n)));
+        let o_mid = o.start + head..o.end - tail;
+        let m_mid = m.start + head..m.end - tail;
+        if o_mid.len().saturating_mul(m_mid.len()) <= MAX_GAP_CELLS {
+            self.lcs_ops(o_mid, m_mid, ops);
+        } else {
+            ops.extend(o_mid.map(LineOp::Remove));
+            ops.extend(m_mid.map(LineOp::Add));
+        }
+        ops.extend((1..=tail).rev().map(|n| LineOp::Keep(o.end - n, m.end - n)));
+    }
+
+    // longest common subsequence of lines, removed lines before added ones
+    fn lcs_ops(&self, o: Range<usize>, m: Range<usize>, ops: &mut Vec<LineOp>) {
+        let (rows, cols) = (o.len(), m.len());
+        // lcs[a][b] is length of lcs of o[a..] and m[b..]
+        let mut lcs = vec![0u32; (rows + 1) * (cols + 1)];
+        let at = |a: usize, b: usize| a * (cols + 1) + b;
+        for a in (0..rows).rev() {
+            for b in (0..cols).rev() {
+                lcs[at(a, b)] = match self.o_line(o.start + a) == self.m_line(m.start + b) {
+                    true => lcs[at(a + 1, b + 1)] + 1,
+                    false => lcs[at(a + 1, b)].max(lcs[at(a, b + 1)]),
+                };
+            }
+        }
+        let (mut a, mut b) = (0, 0);
+        while a < rows || b < cols {
+            if a < rows && b < cols && self.o_line(o.start + a) == self.m_line(m.start + b) {
+                ops.push(LineOp::Keep(o.start + a, m.start + b));
+                (a, b) = (a + 1, b + 1);
+            } else if b == cols || a < rows && lcs[at(a + 1, b)] >= lcs[at(a, b + 1)] {
+                ops.push(LineOp::Remove(o.start + a));
+                a += 1;
+            } else {
+                ops.push(LineOp::Add(m.start + b));
+                b += 1;
+            }
+        }
+    }
+
+    /// Renders delta list as `diff -u` would, nothing if files are the same,
+    /// one line saying so if either file isn't text.
+    pub fn render(&self, o_name: &str, m_name: &str) -> String {
+        if self.original == self.modified.as_slice() {
+            return String::new();
+        }
+        if self.is_binary() {
+            return format!("Binary files {} and {} differ\n", o_name, m_name);
+        }
+        let ops = self.line_ops();
+        let mut out = format!("--- {}\n+++ {}\n", o_name, m_name);
+
+        let changed: Vec<usize> = ops.iter()
+            .enumerate()
+            .filter(|(_, op)| !matches!(op, LineOp::Keep(..)))
+            .map(|(n, _)| n)
+            .collect();
+        let mut n = 0;
+        while n < changed.len() {
+            // changes this close share one hunk
+            let mut last = n;
+            while last + 1 < changed.len() && changed[last + 1] - changed[last] <= 2 * self.context + 1 {
+                last += 1;
+            }
+            let start = changed[n].saturating_sub(self.context);
+            let end = (changed[last] + self.context + 1).min(ops.len());
+            self.render_hunk(&ops, start, end, &mut out);
+            n = last + 1;
+        }
+        out
+    }
+
+    fn render_hunk(&self, ops: &[LineOp], start: usize, end: usize, out: &mut String) {
+        // lines of each file before hunk, and in it
+        let before = |o: bool| ops[..start].iter().filter(|op| Self::in_file(op, o)).count();
+        let count = |o: bool| ops[start..end].iter().filter(|op| Self::in_file(op, o)).count();
+        let range = |o: bool| match count(o) {
+            // empty range names line before it
+            0 => format!("{},0", before(o)),
+            1 => format!("{}", before(o) + 1),
+            len => format!("{},{}", before(o) + 1, len),
+        };
+        out.push_str(&format!("@@ -{} +{} @@\n", range(true), range(false)));
+
+        for op in &ops[start..end] {
+            let (mark, line) = match *op {
+                LineOp::Keep(i, _) => (' ', self.o_line(i)),
+                LineOp::Remove(i) => ('-', self.o_line(i)),
+                LineOp::Add(j) => ('+', self.m_line(j)),
+            };
+            out.push(mark);
+            out.push_str(&String::from_utf8_lossy(line));
+            if !line.ends_with(b"\n") {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    // whether line op shows a line of original, or of modified otherwise
+    fn in_file(op: &LineOp, original: bool) -> bool {
+        match op {
+            LineOp::Keep(..) => true,
+            LineOp::Remove(_) => original,
+            LineOp::Add(_) => !original,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod unified_test {
+    use super::*;
+    use crate::error::Error;
+    use crate::{SignOptions, signature, delta};
+
+    fn unified(original: &str, modified: &str, c_size: usize) -> String {
+        let mut opts = SignOptions::new();
+        opts.chunk_size = Some(c_size);
+        let sign = signature(original.as_bytes(), &opts).unwrap();
+        let list = delta(&sign, modified.as_bytes()).unwrap().list;
+        UnifiedDiff::new(original.as_bytes(), &list).unwrap().render("a.txt", "b.txt")
+    }
+
+    #[test]
+    fn ut_render_works() {
+        let original: String = (1..=12).map(|n| format!("line {}\n", n)).collect();
+        let modified = original.replace("line 2\n", "line two\n").replace("line 11\n", "");
+        let expected = "\
+--- a.txt
++++ b.txt
+@@ -1,5 +1,5 @@
+ line 1
+-line 2
++line two
+ line 3
+ line 4
+ line 5
+@@ -8,5 +8,4 @@
+ line 8
+ line 9
+ line 10
+-line 11
+ line 12
+";
+        // copies of whole lines, or whole file as literal
+        assert_eq!(unified(&original, &modified, 7), expected);
+        assert_eq!(unified(&original, &modified, 1000), expected);
+        // chunks across lines
+        assert_eq!(unified(&original, &modified, 5), expected);
+    }
+
+    #[test]
+    fn ut_renderEdgeCases_works() {
+        assert_eq!(unified("same\n", "same\n", 4), "");
+        assert_eq!(unified("", "new\n", 4), "--- a.txt\n+++ b.txt\n@@ -0,0 +1 @@\n+new\n");
+        assert_eq!(unified("old\n", "", 4), "--- a.txt\n+++ b.txt\n@@ -1 +0,0 @@\n-old\n");
+        assert_eq!(
+            unified("a\nb", "a\nb\n", 4),
+            "--- a.txt\n+++ b.txt\n@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+b\n"
+        );
+        assert_eq!(unified("a\0", "b\0", 4), "Binary files a.txt and b.txt differ\n");
+    }
+
+    #[test]
+    fn ut_newWithBadCopy_fails() {
+        let original = "line 1\nline 2\n".as_bytes();
+        for list in [vec![Change::copy(7, 8)], vec![Change::copy(usize::MAX, 2)]] {
+            assert!(matches!(UnifiedDiff::new(original, &list), Err(Error::CorruptDelta(_))));
+        }
+    }
+}
@@ -0,0 +1,12 @@
+// helpers shared by unit tests
+
+// deterministic pseudo random bytes
+pub fn bytes(len: usize, seed: u64) -> Vec<u8> {
+    let mut x = seed;
+    (0..len).map(|_| {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        x as u8
+    }).collect()
+}
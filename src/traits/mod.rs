@@ -1,6 +1,26 @@
 
 
+/// Weak hash over a sliding window of bytes, cheap to move by one byte.
+/// window is kept by the hash itself, oldest byte first.
+pub trait RollingHash {
+    // appends byte at the end of window
+    fn roll_in(&mut self, byte: u8);
+    // drops oldest byte of window and returns it, None if window is empty
+    fn roll_out(&mut self) -> Option<u8>;
+    fn digest(&self) -> u32;
+    // back to an empty window
+    fn reset(&mut self);
+    fn window_len(&self) -> usize;
+    // bytes currently in window, for computing a strong hash of it
+    fn window(&mut self) -> &[u8];
+}
 
-pub trait CustomError {
-    
-}
\ No newline at end of file
+/// Strong hash of a whole chunk, not rolling.
+/// used to confirm a weak hash match.
+pub trait StrongHash {
+    // full digest length in bytes
+    const DIGEST_LEN: usize;
+    // digest of bytes cut to out.len() bytes, which is at most DIGEST_LEN,
+    // seed 0 gives plain unseeded digest
+    fn sum_into(bytes: &[u8], seed: u64, out: &mut [u8]);
+}
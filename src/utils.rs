@@ -1,30 +1,55 @@
-#![allow(dead_code)]
-#![allow(unused_imports)]
+#![allow(non_snake_case)]
 
-use crate::constants::{ErrKind};
+use std::hash::{BuildHasher, Hasher};
+use std::collections::hash_map::RandomState;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::constants::Val;
 
 pub struct Utility;
 
 impl Utility {
-    pub fn panic(msg: &str) {
-        panic!("{}", msg);
-    }
-
     pub fn get_num_of_chunks(f_size: usize, c_size: usize) -> usize {
         (f_size as f32/c_size as f32).ceil() as usize
     }
-}
 
-pub struct Err;
+    // square root of file size like rsync does, so both chunk count and
+    // chunk size grow slowly with file size, multiple of 8 within bounds
+    pub fn auto_chunk_size(f_size: usize) -> usize {
+        let root = (f_size as f64).sqrt() as usize & !7;
+        root.clamp(Val::MIN_AUTO_C_SIZE, Val::MAX_AUTO_C_SIZE)
+    }
 
-impl Err {
-    pub fn print_err(wh:&str, msg: std::io::Error) {
-        println!("Error! {} {:?}",wh, msg);
+    pub fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
     }
 
-    pub fn handle(wh: &str, err_kind: ErrKind) {
-        match err_kind {
-            ErrKind::FILE_OPEN => Self::print_err(wh, std::io::Error::last_os_error()),
-        }
+    // non zero random number, std hash map keys are randomly seeded per process
+    // and time makes two seeds of one process differ
+    pub fn random_seed() -> u64 {
+        let mut hasher = RandomState::new().build_hasher();
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        hasher.write_u128(nanos);
+        hasher.finish().max(1)
+    }
+}
+
+
+#[cfg(test)]
+mod utils_test {
+    use super::*;
+
+    #[test]
+    fn ut_autoChunkSize_works() {
+        assert_eq!(Utility::auto_chunk_size(0), Val::MIN_AUTO_C_SIZE);
+        assert_eq!(Utility::auto_chunk_size(34), Val::MIN_AUTO_C_SIZE);
+        assert_eq!(Utility::auto_chunk_size(1_000_000), 1000);
+        // 2 GB file gets about 46k chunks instead of half a billion
+        let size = Utility::auto_chunk_size(2_000_000_000);
+        assert_eq!(size, 44720);
+        assert_eq!(size % 8, 0);
+        assert_eq!(Utility::auto_chunk_size(usize::MAX), Val::MAX_AUTO_C_SIZE);
     }
 }